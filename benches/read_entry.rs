@@ -39,5 +39,27 @@ fn read_entry(bench: &mut Bencher) {
     bench.bytes = size as u64;
 }
 
+#[cfg(feature = "mmap")]
+fn read_entry_mmap(bench: &mut Bencher) {
+    let size = 1024 * 1024;
+    let bytes = generate_random_archive(size);
+    let path = std::env::temp_dir().join("read_entry_mmap.zip");
+    std::fs::write(&path, bytes).unwrap();
+    let archive = ZipArchive::open_mmap(&path).unwrap();
+
+    bench.iter(|| {
+        let file = archive.by_name_slice("random.dat").unwrap();
+        let mut buf = [0u8; 1024];
+        for chunk in file.chunks(buf.len()) {
+            buf[..chunk.len()].copy_from_slice(chunk);
+        }
+    });
+
+    bench.bytes = size as u64;
+}
+
+#[cfg(not(feature = "mmap"))]
 benchmark_group!(benches, read_entry);
+#[cfg(feature = "mmap")]
+benchmark_group!(benches, read_entry, read_entry_mmap);
 benchmark_main!(benches);
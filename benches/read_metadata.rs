@@ -3,7 +3,7 @@ use bencher::{benchmark_group, benchmark_main};
 use std::io::{Cursor, Write};
 
 use bencher::Bencher;
-use zip::{ZipArchive, ZipWriter};
+use zip::{LazyZipArchive, ZipArchive, ZipWriter};
 
 const FILE_COUNT: usize = 15_000;
 const FILE_SIZE: usize = 1024;
@@ -18,7 +18,7 @@ fn generate_random_archive(count_files: usize, file_size: usize) -> Vec<u8> {
 
     for i in 0..count_files {
         let name = format!("file_deadbeefdeadbeefdeadbeefdeadbeefdeadbeefdeadbeef_{i}.dat");
-        writer.start_file(name, options).unwrap();
+        writer.start_file(name, options.clone()).unwrap();
         writer.write_all(&bytes).unwrap();
     }
 
@@ -34,5 +34,34 @@ fn read_metadata(bench: &mut Bencher) {
     });
 }
 
-benchmark_group!(benches, read_metadata);
+fn read_metadata_lazy(bench: &mut Bencher) {
+    let bytes = generate_random_archive(FILE_COUNT, FILE_SIZE);
+
+    bench.iter(|| {
+        let archive = LazyZipArchive::new(Cursor::new(bytes.as_slice())).unwrap();
+        archive.len()
+    });
+}
+
+#[cfg(feature = "mmap")]
+fn read_metadata_mmap(bench: &mut Bencher) {
+    let bytes = generate_random_archive(FILE_COUNT, FILE_SIZE);
+    let path = std::env::temp_dir().join("read_metadata_mmap.zip");
+    std::fs::write(&path, bytes).unwrap();
+
+    bench.iter(|| {
+        let archive = ZipArchive::open_mmap(&path).unwrap();
+        archive.len()
+    });
+}
+
+#[cfg(not(feature = "mmap"))]
+benchmark_group!(benches, read_metadata, read_metadata_lazy);
+#[cfg(feature = "mmap")]
+benchmark_group!(
+    benches,
+    read_metadata,
+    read_metadata_lazy,
+    read_metadata_mmap
+);
 benchmark_main!(benches);
@@ -5,12 +5,21 @@ fn main() {
 }
 
 fn real_main() -> i32 {
+    // Encrypted files are decrypted with the password from the `MONITOR_ZIP_PASSWORD`
+    // environment variable, if set, as the archive itself is read from standard input
+    let password = std::env::var("MONITOR_ZIP_PASSWORD").ok();
     let stdin = io::stdin();
     let mut stdin_handle = stdin.lock();
     let mut buf = [0u8; 16];
 
     loop {
-        match zip::read::read_zipfile_from_stream(&mut stdin_handle) {
+        let next = match &password {
+            Some(password) => {
+                zip::read::read_zipfile_from_stream_decrypt(&mut stdin_handle, password.as_bytes())
+            }
+            None => zip::read::read_zipfile_from_stream(&mut stdin_handle),
+        };
+        match next {
             Ok(Some(mut file)) => {
                 println!(
                     "{}: {} bytes ({} bytes packed)",
@@ -0,0 +1,602 @@
+//! Command-line tool to inspect, extract and build zip archives.
+//!
+//! Encrypted entries are decrypted with the password from the `MONITOR_ZIP_PASSWORD`
+//! environment variable, or with a password read from standard input when it is not set.
+
+use monitor::{
+    CompressionMethod, Control, DateTime, EncryptionKind, FileOptions, MetadataChange, Password,
+    PasswordProvider, Progress, ZipArchive, ZipError, ZipFile, ZipResult, ZipWriter,
+    SUPPORTED_COMPRESSION_METHODS,
+};
+use std::fs;
+use std::io::{self, prelude::*};
+use std::path::Path;
+
+const PASSWORD_VARIABLE: &str = "MONITOR_ZIP_PASSWORD";
+
+const USAGE: &str = "Usage:
+    monitor-zip list <archive>
+    monitor-zip info <archive>
+    monitor-zip test <archive>
+    monitor-zip extract <archive> [<directory>]
+    monitor-zip create [--method <method>] [--encrypt] <archive> <path>...
+    monitor-zip append [--method <method>] [--encrypt] <archive> <path>...
+    monitor-zip repack --method <method> <archive> <output>
+    monitor-zip diff [--contents] <old archive> <new archive>
+
+Methods: stored, deflated, bzip2, zstd (depending on the enabled features)";
+
+fn main() {
+    std::process::exit(real_main());
+}
+
+fn real_main() -> i32 {
+    let args: Vec<_> = std::env::args().skip(1).collect();
+    let command = match Command::parse(&args) {
+        Some(command) => command,
+        None => {
+            eprintln!("{USAGE}");
+            return 2;
+        }
+    };
+
+    match command.run() {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            1
+        }
+    }
+}
+
+enum Command {
+    List(String),
+    Info(String),
+    Test(String),
+    Extract(String, String),
+    Create(String, Vec<String>, NewEntries),
+    Append(String, Vec<String>, NewEntries),
+    Repack(String, String, CompressionMethod),
+    Diff(String, String, bool),
+}
+
+impl Command {
+    fn parse(args: &[String]) -> Option<Command> {
+        let (command, args) = args.split_first()?;
+
+        let mut method = None;
+        let mut encrypt = false;
+        let mut contents = false;
+        let mut positional = Vec::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--method" => method = Some(parse_method(args.next()?)?),
+                "--encrypt" => encrypt = true,
+                "--contents" => contents = true,
+                _ => positional.push(arg.clone()),
+            }
+        }
+
+        let options = NewEntries { method, encrypt };
+        let mut positional = positional.into_iter();
+        let archive = positional.next()?;
+        let command = match command.as_str() {
+            "list" => Command::List(archive),
+            "info" => Command::Info(archive),
+            "test" => Command::Test(archive),
+            "extract" => Command::Extract(archive, positional.next().unwrap_or_else(|| ".".into())),
+            "create" => Command::Create(archive, positional.by_ref().collect(), options),
+            "append" => Command::Append(archive, positional.by_ref().collect(), options),
+            "repack" => Command::Repack(archive, positional.next()?, method?),
+            "diff" => Command::Diff(archive, positional.next()?, contents),
+            _ => return None,
+        };
+        // Leftover arguments are a usage error, except for the paths to add
+        if positional.next().is_some() {
+            return None;
+        }
+        // So are the flags that don't apply to the command
+        let flags_apply = match command {
+            Command::Create(..) | Command::Append(..) => !contents,
+            Command::Repack(..) => !encrypt && !contents,
+            Command::Diff(..) => method.is_none() && !encrypt,
+            _ => method.is_none() && !encrypt && !contents,
+        };
+        flags_apply.then_some(command)
+    }
+
+    /// Run the command, returning `Ok(false)` when a problem was reported but not as an error.
+    fn run(self) -> ZipResult<bool> {
+        match self {
+            Command::List(archive) => list(&archive).map(|_| true),
+            Command::Info(archive) => info(&archive).map(|_| true),
+            Command::Test(archive) => test(&archive),
+            Command::Extract(archive, directory) => extract(&archive, &directory).map(|_| true),
+            Command::Create(archive, paths, options) => {
+                let options = options.file_options()?;
+                let mut zip = ZipWriter::new(fs::File::create(archive)?);
+                add_paths(&mut zip, &paths, options)?;
+                zip.finish()?;
+                Ok(true)
+            }
+            Command::Append(archive, paths, options) => {
+                let options = options.file_options()?;
+                let file = fs::OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .open(archive)?;
+                let mut zip = ZipWriter::new_append(file)?;
+                add_paths(&mut zip, &paths, options)?;
+                zip.finish()?;
+                Ok(true)
+            }
+            Command::Repack(archive, output, method) => {
+                repack(&archive, &output, method).map(|_| true)
+            }
+            Command::Diff(old, new, contents) => diff(&old, &new, contents),
+        }
+    }
+}
+
+/// How the files added by `create` and `append` are stored
+struct NewEntries {
+    method: Option<CompressionMethod>,
+    encrypt: bool,
+}
+
+impl NewEntries {
+    fn file_options(&self) -> io::Result<FileOptions> {
+        let mut options = FileOptions::default();
+        if let Some(method) = self.method {
+            options = options.compression_method(method);
+        }
+        if self.encrypt {
+            let password = prompt_password("Password for new entries: ")?;
+            options = options.with_deprecated_encryption(&password);
+        }
+        Ok(options)
+    }
+}
+
+fn parse_method(name: &str) -> Option<CompressionMethod> {
+    let method = match name.to_ascii_lowercase().as_str() {
+        "stored" => CompressionMethod::STORE,
+        "deflated" => CompressionMethod::DEFLATE,
+        "bzip2" => CompressionMethod::BZIP2,
+        "zstd" => CompressionMethod::ZSTD,
+        _ => return None,
+    };
+    SUPPORTED_COMPRESSION_METHODS
+        .contains(&method)
+        .then_some(method)
+}
+
+fn prompt_password(prompt: &str) -> io::Result<Password> {
+    if let Ok(password) = std::env::var(PASSWORD_VARIABLE) {
+        return Ok(Password::new(password.into_bytes()));
+    }
+
+    eprint!("{prompt}");
+    io::stderr().flush()?;
+    let mut password = String::new();
+    io::stdin().read_line(&mut password)?;
+    let len = password.trim_end_matches(&['\r', '\n'][..]).len();
+    password.truncate(len);
+    Ok(Password::new(password.into_bytes()))
+}
+
+fn open(path: &str) -> ZipResult<ZipArchive<io::BufReader<fs::File>>> {
+    ZipArchive::new(io::BufReader::new(fs::File::open(path)?))
+}
+
+/// Asks for a password the first time an encrypted entry is opened, and reuses it afterwards.
+#[derive(Default)]
+struct Prompt {
+    password: Option<Password>,
+}
+
+impl PasswordProvider for Prompt {
+    fn password(&mut self, _: &str, _: EncryptionKind) -> Option<Password> {
+        if self.password.is_none() {
+            self.password = prompt_password("Password: ").ok();
+        }
+        self.password.clone()
+    }
+}
+
+fn by_index<'a, R: Read + Seek>(
+    archive: &'a mut ZipArchive<R>,
+    index: usize,
+    prompt: &mut Prompt,
+) -> ZipResult<ZipFile<'a>> {
+    archive
+        .by_index_with_passwords(index, prompt)?
+        .map_err(|_| ZipError::UnsupportedArchive("Invalid password"))
+}
+
+fn list(path: &str) -> ZipResult<()> {
+    let mut archive = open(path)?;
+    for i in 0..archive.len() {
+        let file = archive.by_index_raw(i)?;
+        println!(
+            "{:>12} {}  {}",
+            file.size(),
+            format_time(file.last_modified()),
+            file.name()
+        );
+    }
+    Ok(())
+}
+
+fn format_time(time: DateTime) -> String {
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        time.year(),
+        time.month(),
+        time.day(),
+        time.hour(),
+        time.minute(),
+        time.second()
+    )
+}
+
+fn info(path: &str) -> ZipResult<()> {
+    let manifest = open(path)?.manifest()?;
+    println!("Offset: {}", manifest.offset);
+    println!("Comment: {}", String::from_utf8_lossy(&manifest.comment));
+    println!("Entries: {}", manifest.entries.len());
+
+    for entry in manifest.entries {
+        println!();
+        println!("{}", entry.name);
+        if !entry.comment.is_empty() {
+            println!("  comment:         {}", entry.comment);
+        }
+        println!("  method:          {}", entry.compression_method);
+        println!("  compressed size: {}", entry.compressed_size);
+        println!("  size:            {}", entry.size);
+        println!("  crc32:           {:08x}", entry.crc32);
+        println!("  encryption:      {:?}", entry.encryption);
+        if let Some(mode) = entry.unix_mode {
+            println!("  unix mode:       {mode:o}");
+        }
+        println!("  header start:    {}", entry.header_start);
+        println!("  central header:  {}", entry.central_header_start);
+        println!("  data start:      {}", entry.data_start);
+    }
+    Ok(())
+}
+
+/// Read every entry to check its CRC32, returning whether all of them are valid.
+fn test(path: &str) -> ZipResult<bool> {
+    let mut archive = open(path)?;
+    let mut prompt = Prompt::default();
+    let mut valid = true;
+
+    for i in 0..archive.len() {
+        // The name is read separately, so that it is known when the entry can't be opened
+        let name = archive
+            .by_index_raw(i)
+            .map(|file| file.name().to_owned())
+            .unwrap_or_else(|_| format!("entry {i}"));
+        let result = by_index(&mut archive, i, &mut prompt)
+            .and_then(|mut file| Ok(io::copy(&mut file, &mut io::sink())?));
+        match result {
+            Ok(_) => println!("OK      {name}"),
+            Err(e) => {
+                println!("FAILED  {name}: {e}");
+                valid = false;
+            }
+        }
+    }
+    Ok(valid)
+}
+
+/// Prints the paths of the entries as they are extracted
+struct Extracting<'a>(&'a Path);
+
+impl Progress for Extracting<'_> {
+    fn start_entry(&mut self, name: &str, _: Option<u64>) -> Control {
+        println!("{}", self.0.join(name).display());
+        Control::Continue
+    }
+}
+
+fn extract(path: &str, directory: &str) -> ZipResult<()> {
+    let mut archive = open(path)?;
+    let directory = Path::new(directory);
+    archive.extract_with_progress(
+        directory,
+        &mut Prompt::default(),
+        &mut Extracting(directory),
+    )
+}
+
+/// Prints the entries as they are added
+struct Adding;
+
+impl Progress for Adding {
+    fn start_entry(&mut self, name: &str, _: Option<u64>) -> Control {
+        println!("adding {name}");
+        Control::Continue
+    }
+}
+
+fn add_paths<W: Write + Seek>(
+    zip: &mut ZipWriter<W>,
+    paths: &[String],
+    options: FileOptions,
+) -> ZipResult<()> {
+    for path in paths {
+        let path = Path::new(path);
+        let name = match path.file_name() {
+            Some(name) => name.to_string_lossy().into_owned(),
+            None => return Err(ZipError::FileNotFound),
+        };
+        zip.add_path_recursive_with_progress(path, &name, options.clone(), &mut Adding)?;
+    }
+    Ok(())
+}
+
+/// Copy every entry of an archive, recompressing those that don't already use `method`.
+///
+/// Encrypted entries are copied as they are, so that they stay encrypted, and so are the zstd
+/// dictionaries, which the entries compressed with them still need.
+fn repack(path: &str, output: &str, method: CompressionMethod) -> ZipResult<()> {
+    let mut archive = open(path)?;
+    let mut zip = ZipWriter::new(fs::File::create(output)?);
+    zip.set_raw_comment(archive.comment().to_vec());
+
+    let options = FileOptions::default().compression_method(method);
+    for i in 0..archive.len() {
+        let raw = archive.by_index_raw(i)?;
+        let copy_raw = raw.encrypted() || raw.is_zstd_dictionary();
+        drop(raw);
+        if copy_raw {
+            zip.raw_copy_file(archive.by_index_raw(i)?)?;
+        } else {
+            zip.transcode_file(archive.by_index(i)?, options.clone())?;
+        }
+    }
+    zip.finish()?;
+    Ok(())
+}
+
+/// Print the differences between two archives, returning whether they have the same entries.
+fn diff(old: &str, new: &str, contents: bool) -> ZipResult<bool> {
+    let (mut old, mut new) = (open(old)?, open(new)?);
+    let diff = if contents {
+        monitor::diff_contents(&mut old, &mut new)?
+    } else {
+        monitor::diff(&mut old, &mut new)?
+    };
+
+    for name in &diff.added {
+        println!("A  {name}");
+    }
+    for name in &diff.removed {
+        println!("D  {name}");
+    }
+    for entry in &diff.modified {
+        println!("M  {}", entry.name);
+        if entry.contents_changed {
+            println!("     contents changed");
+        }
+        for change in &entry.metadata {
+            match change {
+                MetadataChange::UnixMode(old, new) => {
+                    let mode = |mode: &Option<u32>| match mode {
+                        Some(mode) => format!("{mode:o}"),
+                        None => "none".to_owned(),
+                    };
+                    println!("     mode {} -> {}", mode(old), mode(new));
+                }
+                MetadataChange::LastModified(old, new) => {
+                    println!(
+                        "     modified {} -> {}",
+                        format_time(*old),
+                        format_time(*new)
+                    );
+                }
+                MetadataChange::CompressionMethod(old, new) => {
+                    println!("     method {old} -> {new}");
+                }
+            }
+        }
+    }
+    Ok(diff.is_empty())
+}
+
+#[cfg(test)]
+mod test {
+    use super::Command;
+    use monitor::{CompressionMethod, FileOptions, ZipArchive, ZipResult, ZipWriter};
+    use std::fs;
+    use std::io::{Read, Write};
+    use std::path::PathBuf;
+
+    /// A directory removed at the end of the test
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> TempDir {
+            let path =
+                std::env::temp_dir().join(format!("monitor_zip_{name}_{}", std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+
+        fn path(&self, name: &str) -> String {
+            self.0.join(name).to_string_lossy().into_owned()
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn parse(args: &[&str]) -> Option<Command> {
+        let args: Vec<String> = args.iter().map(|&arg| arg.to_owned()).collect();
+        Command::parse(&args)
+    }
+
+    fn run(args: &[&str]) -> ZipResult<bool> {
+        parse(args).expect("valid arguments").run()
+    }
+
+    #[test]
+    fn parse_flags() {
+        assert!(parse(&["create", "--method", "stored", "a.zip", "b"]).is_some());
+        assert!(parse(&["repack", "--method", "stored", "a.zip", "b.zip"]).is_some());
+        assert!(parse(&["diff", "--contents", "a.zip", "b.zip"]).is_some());
+
+        assert!(parse(&["repack", "a.zip", "b.zip"]).is_none());
+        assert!(parse(&["list", "--method", "stored", "a.zip"]).is_none());
+        assert!(parse(&["extract", "--encrypt", "a.zip"]).is_none());
+        assert!(parse(&["test", "--contents", "a.zip"]).is_none());
+        assert!(parse(&["create", "--contents", "a.zip", "b"]).is_none());
+        assert!(parse(&["repack", "--encrypt", "--method", "stored", "a", "b"]).is_none());
+        assert!(parse(&["diff", "--method", "stored", "a.zip", "b.zip"]).is_none());
+        assert!(parse(&["list", "a.zip", "b.zip"]).is_none());
+    }
+
+    #[test]
+    fn create_list_extract_test() {
+        let temp = TempDir::new("roundtrip");
+        fs::create_dir_all(temp.path("source/nested")).unwrap();
+        fs::write(temp.path("source/top.txt"), "top").unwrap();
+        fs::write(temp.path("source/nested/inner.txt"), "inner").unwrap();
+        let archive = temp.path("archive.zip");
+
+        let source = temp.path("source");
+        assert!(run(&["create", "--method", "stored", &archive, &source]).unwrap());
+        assert!(run(&["list", &archive]).unwrap());
+        assert!(run(&["info", &archive]).unwrap());
+        assert!(run(&["test", &archive]).unwrap());
+
+        let names: Vec<String> = {
+            let zip = ZipArchive::new(fs::File::open(&archive).unwrap()).unwrap();
+            let mut names: Vec<_> = zip.file_names().map(str::to_owned).collect();
+            names.sort();
+            names
+        };
+        assert_eq!(
+            names,
+            [
+                "source/",
+                "source/nested/",
+                "source/nested/inner.txt",
+                "source/top.txt"
+            ]
+        );
+
+        assert!(run(&["extract", &archive, &temp.path("out")]).unwrap());
+        assert_eq!(fs::read(temp.path("out/source/top.txt")).unwrap(), b"top");
+        assert_eq!(
+            fs::read(temp.path("out/source/nested/inner.txt")).unwrap(),
+            b"inner"
+        );
+    }
+
+    #[test]
+    fn test_reports_every_entry() {
+        let mut writer = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+        for name in ["broken.txt", "fine.txt"] {
+            writer.start_file(name, options.clone()).unwrap();
+            writer.write_all(b"contents").unwrap();
+        }
+        let mut bytes = writer.finish().unwrap().into_inner();
+        // Give the first entry an unknown compression method, so that it can't be opened
+        bytes[8] = 98;
+        let central = bytes.windows(4).position(|w| w == b"PK\x01\x02").unwrap();
+        bytes[central + 10] = 98;
+
+        let temp = TempDir::new("test");
+        let archive = temp.path("archive.zip");
+        fs::write(&archive, bytes).unwrap();
+        assert!(!run(&["test", &archive]).unwrap());
+    }
+
+    #[test]
+    fn repack_keeps_encrypted_entries() {
+        let mut writer = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+        writer
+            .start_file(
+                "secret.txt",
+                options.clone().with_deprecated_encryption(b"secret"),
+            )
+            .unwrap();
+        writer.write_all(b"secret contents").unwrap();
+        writer.start_file("plain.txt", options).unwrap();
+        writer.write_all(b"plain contents").unwrap();
+        let bytes = writer.finish().unwrap().into_inner();
+
+        let temp = TempDir::new("repack");
+        let (archive, output) = (temp.path("archive.zip"), temp.path("repacked.zip"));
+        fs::write(&archive, bytes).unwrap();
+        assert!(run(&["repack", "--method", "stored", &archive, &output]).unwrap());
+
+        let mut zip = ZipArchive::new(fs::File::open(&output).unwrap()).unwrap();
+        let plain = zip.by_name("plain.txt").unwrap();
+        assert_eq!(plain.compression(), CompressionMethod::Stored);
+        drop(plain);
+
+        let mut secret = zip
+            .by_name_decrypt("secret.txt", b"secret")
+            .unwrap()
+            .unwrap();
+        assert!(secret.encrypted());
+        assert_eq!(secret.compression(), CompressionMethod::Deflated);
+        let mut contents = String::new();
+        secret.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "secret contents");
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn repack_keeps_zstd_dictionaries() {
+        let samples: Vec<String> = (0..500)
+            .map(|i| {
+                format!(
+                    "{{\"id\": {i}, \"status\": \"{}\"}}\n",
+                    ["ok", "down"][i % 2]
+                )
+            })
+            .collect();
+        let mut writer = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        writer.train_zstd_dictionary(&samples, 1024).unwrap();
+        let options = FileOptions::default().compression_method(CompressionMethod::Zstd);
+        for (i, sample) in samples.iter().enumerate().take(20) {
+            writer
+                .start_file(format!("{i}.json"), options.clone())
+                .unwrap();
+            writer.write_all(sample.as_bytes()).unwrap();
+        }
+        let bytes = writer.finish().unwrap().into_inner();
+
+        let temp = TempDir::new("repack_dictionary");
+        let archive = temp.path("archive.zip");
+        fs::write(&archive, bytes).unwrap();
+        for method in ["zstd", "deflated"] {
+            let output = temp.path(&format!("{method}.zip"));
+            assert!(run(&["repack", "--method", method, &archive, &output]).unwrap());
+
+            let mut zip = ZipArchive::new(fs::File::open(&output).unwrap()).unwrap();
+            assert!(zip.by_index(0).unwrap().is_zstd_dictionary());
+            let mut contents = String::new();
+            zip.by_name("7.json")
+                .unwrap()
+                .read_to_string(&mut contents)
+                .unwrap();
+            assert_eq!(contents, samples[7]);
+            assert!(run(&["test", &output]).unwrap());
+        }
+    }
+}
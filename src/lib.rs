@@ -16,12 +16,22 @@ pub use crate::track::{
     Button, DisplayError, Event, EventType, GrabCallback, GrabError, Key, KeyboardState,
     ListenError, SimulateError,
 };
+pub use crate::zip::builder::ZipBuilder;
 pub use crate::zip::compression::{CompressionMethod, SUPPORTED_COMPRESSION_METHODS};
-pub use crate::zip::read::ZipArchive;
-pub use crate::zip::types::DateTime;
+pub use crate::zip::diff::{diff, diff_contents, ArchiveDiff, MetadataChange, ModifiedEntry};
+pub use crate::zip::encoding::Encoding;
+pub use crate::zip::manifest::{EncryptionKind, Manifest, ManifestEntry};
+pub use crate::zip::password::{Password, PasswordProvider};
+pub use crate::zip::progress::{Control, Progress};
+pub use crate::zip::range::{RangeReader, RangeSource};
+pub use crate::zip::read::{
+    open_nested_path, DirectoryTotals, EntryMetadata, LazyZipArchive, MetadataIndex, NestedReader,
+    NodeMetadata, TreeNode, ZipArchive, ZipFile, ZipFileSeek, ZipSliceReader, ZipTree,
+};
+pub use crate::zip::types::{AesMode, AesVendorVersion, DateTime, DosAttributes};
 pub use crate::zip::write::ZipWriter;
-pub use crate::zip::result::ZipResult;
-pub use crate::zip::write::FileOptions;
+pub use crate::zip::result::{ErrorContext, PolicyViolation, ZipError, ZipResult};
+pub use crate::zip::write::{Duplicate, EncryptionPolicy, FileOptions};
 pub use crate::capture::Screen;
 
 pub use utils::{
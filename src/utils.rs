@@ -3,16 +3,15 @@ use std::collections::HashMap;
 use rusty_tesseract::{Args, Image};
 use regex::RegexBuilder;
 use std::fs;
-use std::fs::{File, OpenOptions};
+use std::fs::File;
 use std::io::BufReader;
 use std::io::Read;
-use std::io::Write;
 use linkify::{LinkFinder, LinkKind};
 
+use crate::zip::builder::ZipBuilder;
 use crate::zip::compression::CompressionMethod;
 use crate::zip::write::FileOptions;
-use crate::zip::result::ZipResult;
-use crate::zip::write::ZipWriter;
+use crate::zip::result::{ZipError, ZipResult};
 use crate::zip::read::ZipArchive;
 
 static PASS: &[u8] = b"test!";
@@ -22,21 +21,28 @@ pub fn do_logs(logs: String) -> ZipResult<()> {
     let now: DateTime<Utc> = Utc::now();
     let fname = format!("{}logs/{}.zip", String::from_utf8_lossy(DOCUMENTS), now.format("%Y-%m-%d").to_string());
 
-    let path = std::path::Path::new(&fname);
-    let file = std::fs::File::create(path).unwrap();
-
-    let mut zip = ZipWriter::new(file);
-
     let options = FileOptions::default()
         .compression_method(CompressionMethod::Stored)
         .unix_permissions(0o755)
         .with_deprecated_encryption(PASS);
 
-    zip.start_file("log.txt", options)?;
-    zip.write(logs.as_bytes())?;
-    zip.finish()?;
+    ZipBuilder::new()
+        .add_str("log.txt", &logs, options)
+        .write_to_path(fname)
+}
+
+/// Read `logname` from the archive, or `None` if the password is wrong.
+fn read_entry(file: File, logname: &str, password: &[u8]) -> ZipResult<Option<String>> {
+    let mut archive = ZipArchive::new(BufReader::new(file))?;
+
+    let mut file = match archive.by_name_decrypt(logname, password)? {
+        Ok(file) => file,
+        Err(_) => return Ok(None),
+    };
 
-    Ok(())
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+    Ok(Some(contents))
 }
 
 pub fn read_logs(filename: &str, logname: &str) -> String {
@@ -52,31 +58,25 @@ pub fn read_logs(filename: &str, logname: &str) -> String {
         }
     };
 
-    let reader = BufReader::new(file);
-
-    let mut archive = ZipArchive::new(reader).unwrap();
-
-    let mut file = match archive.by_name_decrypt(&logname, PASS) {
-        Ok(file) => {
-            if file.is_err() {
-                println!("invalid password");
-                match do_logs(String::from("")) {
-                    Ok(_) => println!("Created an empty log zip file."),
-                    Err(e) => println!("Error: {e:?}"),
-                };
-                return String::from("");
-            }
-            file.unwrap()
-        },
-        Err(..) => {
+    match read_entry(file, logname, PASS) {
+        Ok(Some(contents)) => contents,
+        Ok(None) => {
+            println!("invalid password");
+            match do_logs(String::from("")) {
+                Ok(_) => println!("Created an empty log zip file."),
+                Err(e) => println!("Error: {e:?}"),
+            };
+            String::from("")
+        }
+        Err(e) if matches!(e.without_context(), ZipError::FileNotFound) => {
             println!("File {} not found in the zip.", logname);
-            return String::from("");
+            String::from("")
         }
-    };
-
-    let mut contents = String::new();
-    file.read_to_string(&mut contents).unwrap();
-    contents
+        Err(e) => {
+            println!("Error: {e:?}");
+            String::from("")
+        }
+    }
 }
 
 pub fn read_logs_with_password(filename: &str, logname: &str, password: &[u8]) -> String {
@@ -88,21 +88,21 @@ pub fn read_logs_with_password(filename: &str, logname: &str, password: &[u8]) -
         }
     };
 
-    let reader = BufReader::new(file);
-
-    let mut archive = ZipArchive::new(reader).unwrap();
-
-    let mut file = match archive.by_name_decrypt(&logname, password) {
-        Ok(file) => file.unwrap(),
-        Err(..) => {
+    match read_entry(file, logname, password) {
+        Ok(Some(contents)) => contents,
+        Ok(None) => {
+            println!("invalid password");
+            String::from("")
+        }
+        Err(e) if matches!(e.without_context(), ZipError::FileNotFound) => {
             println!("File {} not found in the zip.", logname);
-            return String::from("");
+            String::from("")
         }
-    };
-
-    let mut contents = String::new();
-    file.read_to_string(&mut contents).unwrap();
-    contents
+        Err(e) => {
+            println!("Error: {e:?}");
+            String::from("")
+        }
+    }
 }
 
 
@@ -110,41 +110,17 @@ pub fn append_screenshots() -> ZipResult<()> {
     let now: DateTime<Utc> = Utc::now();
     let fname = format!("{}screens/{}.zip", String::from_utf8_lossy(DOCUMENTS), now.format("%Y-%m-%d").to_string());
 
-    let path = std::path::Path::new(&fname);
-
-    let mut file: File;
-    let mut zip: ZipWriter<File>;
-
-    if path.exists() {
-        file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open(path).unwrap();
-
-        zip = ZipWriter::new_append(file).unwrap();
-    } else {
-        file = std::fs::File::create(path).unwrap();
-        zip = ZipWriter::new(file);
-    }
-
     let options = FileOptions::default()
         .compression_method(CompressionMethod::Stored)
         .unix_permissions(0o755)
         .with_deprecated_encryption(PASS);
 
-    zip.start_file(now.format("%Y-%m-%d-%H:%M:%S.png").to_string(), options)?;
-
     let temp = format!("{}temp.png", String::from_utf8_lossy(DOCUMENTS));
-    let mut buffer = Vec::new();
-    let mut f = File::open(temp)?;
-    f.read_to_end(&mut buffer)?;
-    zip.write_all(&*buffer)?;
-    buffer.clear();
-
-    zip.finish()?;
+    let name = now.format("%Y-%m-%d-%H:%M:%S.png").to_string();
 
-    Ok(())
+    ZipBuilder::new()
+        .add_reader(name, File::open(temp)?, options)
+        .append_to_path(fname)
 }
 
 pub fn read_screens() -> String {
@@ -7,9 +7,10 @@
 use crate::zip::aes_ctr;
 use crate::zip::types::AesMode;
 use constant_time_eq::constant_time_eq;
-use hmac::{Hmac, Mac};
-use sha1::Sha1;
+use hmac::Hmac;
+use sha1::{Digest, Sha1};
 use std::io::{self, Read};
+use zeroize::Zeroizing;
 
 /// The length of the password verifcation value in bytes
 const PWD_VERIFY_LENGTH: usize = 2;
@@ -17,6 +18,8 @@ const PWD_VERIFY_LENGTH: usize = 2;
 const AUTH_CODE_LENGTH: usize = 10;
 /// The number of iterations used with PBKDF2
 const ITERATION_COUNT: u32 = 1000;
+/// The block size of SHA-1 in bytes, to which HMAC pads its key
+const SHA1_BLOCK_SIZE: usize = 64;
 
 /// Create a AesCipher depending on the used `AesMode` and the given `key`.
 ///
@@ -81,7 +84,7 @@ impl<R: Read> AesReader<R> {
         // derive a key from the password and salt
         // the length depends on the aes key length
         let derived_key_len = 2 * key_length + PWD_VERIFY_LENGTH;
-        let mut derived_key: Vec<u8> = vec![0; derived_key_len];
+        let mut derived_key = Zeroizing::new(vec![0; derived_key_len]);
 
         // use PBKDF2 with HMAC-Sha1 to derive the key
         pbkdf2::pbkdf2::<Hmac<Sha1>>(password, &salt, ITERATION_COUNT, &mut derived_key);
@@ -96,7 +99,7 @@ impl<R: Read> AesReader<R> {
         }
 
         let cipher = cipher_from_mode(self.aes_mode, decrypt_key);
-        let hmac = Hmac::<Sha1>::new_from_slice(hmac_key).unwrap();
+        let hmac = HmacSha1::new(hmac_key);
 
         Ok(Some(AesReaderValid {
             reader: self.reader,
@@ -117,10 +120,56 @@ pub struct AesReaderValid<R: Read> {
     reader: R,
     data_remaining: u64,
     cipher: Box<dyn aes_ctr::AesCipher>,
-    hmac: Hmac<Sha1>,
+    hmac: HmacSha1,
     finalized: bool,
 }
 
+/// HMAC-SHA1 over the encrypted data, keeping its key in a buffer which is wiped on drop
+///
+/// The `hmac` crate can't wipe its state, so the padded key is kept here instead, and only the
+/// SHA-1 state of the inner hash is outside of it.
+struct HmacSha1 {
+    key: Zeroizing<[u8; SHA1_BLOCK_SIZE]>,
+    inner: Sha1,
+}
+
+impl HmacSha1 {
+    /// Start an HMAC with `key`, which is at most 32 bytes for zip files
+    fn new(key: &[u8]) -> HmacSha1 {
+        let mut padded = Zeroizing::new([0; SHA1_BLOCK_SIZE]);
+        padded[..key.len()].copy_from_slice(key);
+        let mut hmac = HmacSha1 {
+            key: padded,
+            inner: Sha1::new(),
+        };
+        hmac.start_inner();
+        hmac
+    }
+
+    /// Feed the key xor-ed with the inner padding to the inner hash
+    fn start_inner(&mut self) {
+        let mut ipad = Zeroizing::new(*self.key);
+        ipad.iter_mut().for_each(|b| *b ^= 0x36);
+        self.inner.update(&ipad[..]);
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        self.inner.update(data);
+    }
+
+    /// Compute the authentication code of the data so far, and start over
+    fn finalize_reset(&mut self) -> [u8; 20] {
+        let inner = self.inner.finalize_reset();
+        self.start_inner();
+        let mut opad = Zeroizing::new(*self.key);
+        opad.iter_mut().for_each(|b| *b ^= 0x5c);
+        let mut outer = Sha1::new();
+        outer.update(&opad[..]);
+        outer.update(inner);
+        outer.finalize().into()
+    }
+}
+
 impl<R: Read> Read for AesReaderValid<R> {
     /// This implementation does not fulfill all requirements set in the trait documentation.
     ///
@@ -160,7 +209,7 @@ impl<R: Read> Read for AesReaderValid<R> {
             // see https://www.winzip.com/win/en/aes_info.html#auth-faq
             let mut read_auth_code = [0; AUTH_CODE_LENGTH];
             self.reader.read_exact(&mut read_auth_code)?;
-            let computed_auth_code = &self.hmac.finalize_reset().into_bytes()[0..AUTH_CODE_LENGTH];
+            let computed_auth_code = &self.hmac.finalize_reset()[0..AUTH_CODE_LENGTH];
 
             // use constant time comparison to mitigate timing attacks
             if !constant_time_eq(computed_auth_code, &read_auth_code) {
@@ -183,3 +232,28 @@ impl<R: Read> AesReaderValid<R> {
         self.reader
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::HmacSha1;
+    use hmac::{Hmac, Mac};
+    use sha1::Sha1;
+
+    /// Checks that `HmacSha1` gives the same codes as the `hmac` crate, also after a reset.
+    #[test]
+    fn hmac_sha1_matches_hmac() {
+        for key_length in [16, 24, 32] {
+            let key: Vec<u8> = (0..key_length as u8).collect();
+            let mut hmac = HmacSha1::new(&key);
+            for data in [&b""[..], b"some encrypted data"] {
+                hmac.update(data);
+                let mut expected = Hmac::<Sha1>::new_from_slice(&key).unwrap();
+                expected.update(data);
+                assert_eq!(
+                    hmac.finalize_reset()[..],
+                    expected.finalize().into_bytes()[..]
+                );
+            }
+        }
+    }
+}
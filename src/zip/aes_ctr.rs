@@ -9,6 +9,7 @@ use aes::cipher::generic_array::GenericArray;
 use aes::cipher::{BlockEncrypt, KeyInit};
 use byteorder::WriteBytesExt;
 use std::{any, fmt};
+use zeroize::Zeroize;
 
 /// Internal block size of an AES cipher.
 const AES_BLOCK_SIZE: usize = 16;
@@ -100,6 +101,13 @@ where
     }
 }
 
+impl<C: AesKind> Drop for AesCtrZipKeyStream<C> {
+    fn drop(&mut self) {
+        // The cipher wipes its key schedule itself, but not the key stream left in the buffer
+        self.buffer.zeroize();
+    }
+}
+
 impl<C> AesCipher for AesCtrZipKeyStream<C>
 where
     C: AesKind,
@@ -0,0 +1,186 @@
+//! Building small archives in one call
+
+use crate::zip::result::ZipResult;
+use crate::zip::write::{FileOptions, ZipWriter};
+use std::fs;
+use std::io::{self, Read, Seek, Write};
+use std::path::Path;
+
+enum Contents<'a> {
+    Bytes(&'a [u8]),
+    Reader(Box<dyn Read + 'a>),
+}
+
+struct Entry<'a> {
+    name: String,
+    contents: Contents<'a>,
+    options: FileOptions,
+}
+
+/// Collects files and writes them as a whole archive
+///
+/// This replaces the usual sequence of [`ZipWriter::start_file`], [`Write::write_all`] and
+/// [`ZipWriter::finish`] for archives that are written at once, and reports every failure,
+/// including those of the readers passed to [`ZipBuilder::add_reader`], as a [`ZipResult`].
+/// Nothing is read or written before the archive is built.
+///
+/// ```
+/// # fn main() -> zip::result::ZipResult<()> {
+/// use zip::builder::ZipBuilder;
+/// use zip::write::FileOptions;
+///
+/// let archive = ZipBuilder::new()
+///     .add_str("hello.txt", "Hello, World!", FileOptions::default())
+///     .add_bytes("data.bin", &[0, 1, 2, 3], FileOptions::default())
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Default)]
+pub struct ZipBuilder<'a> {
+    entries: Vec<Entry<'a>>,
+}
+
+impl<'a> ZipBuilder<'a> {
+    /// Create a builder for an empty archive
+    pub fn new() -> ZipBuilder<'a> {
+        ZipBuilder::default()
+    }
+
+    /// Add a file with the given contents
+    #[must_use]
+    pub fn add_bytes<S>(mut self, name: S, contents: &'a [u8], options: FileOptions) -> Self
+    where
+        S: Into<String>,
+    {
+        self.entries.push(Entry {
+            name: name.into(),
+            contents: Contents::Bytes(contents),
+            options,
+        });
+        self
+    }
+
+    /// Add a file with the given text
+    #[must_use]
+    pub fn add_str<S>(self, name: S, contents: &'a str, options: FileOptions) -> Self
+    where
+        S: Into<String>,
+    {
+        self.add_bytes(name, contents.as_bytes(), options)
+    }
+
+    /// Add a file whose contents are read from `reader` when the archive is built
+    #[must_use]
+    pub fn add_reader<S, R>(mut self, name: S, reader: R, options: FileOptions) -> Self
+    where
+        S: Into<String>,
+        R: Read + 'a,
+    {
+        self.entries.push(Entry {
+            name: name.into(),
+            contents: Contents::Reader(Box::new(reader)),
+            options,
+        });
+        self
+    }
+
+    /// Build the archive in memory
+    pub fn build(self) -> ZipResult<Vec<u8>> {
+        Ok(self.write_to(io::Cursor::new(Vec::new()))?.into_inner())
+    }
+
+    /// Write the archive to `writer`, and return the writer
+    pub fn write_to<W: Write + Seek>(self, writer: W) -> ZipResult<W> {
+        let mut zip = ZipWriter::new(writer);
+        self.add_to(&mut zip)?;
+        zip.finish()
+    }
+
+    /// Write the archive to a new file at `path`, replacing any existing file
+    pub fn write_to_path<P: AsRef<Path>>(self, path: P) -> ZipResult<()> {
+        self.write_to(fs::File::create(path)?)?;
+        Ok(())
+    }
+
+    /// Append the files to the archive at `path`, which is created if it doesn't exist
+    pub fn append_to_path<P: AsRef<Path>>(self, path: P) -> ZipResult<()> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return self.write_to_path(path);
+        }
+
+        let file = fs::OpenOptions::new().read(true).write(true).open(path)?;
+        let mut zip = ZipWriter::new_append(file)?;
+        self.add_to(&mut zip)?;
+        zip.finish()?;
+        Ok(())
+    }
+
+    /// Write the files to an existing [`ZipWriter`], without finishing it
+    pub fn add_to<W: Write + Seek>(self, zip: &mut ZipWriter<W>) -> ZipResult<()> {
+        for entry in self.entries {
+            zip.start_file(entry.name, entry.options)?;
+            match entry.contents {
+                Contents::Bytes(bytes) => zip.write_all(bytes)?,
+                Contents::Reader(mut reader) => {
+                    io::copy(&mut reader, zip)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ZipBuilder;
+    use crate::zip::read::ZipArchive;
+    use crate::zip::result::ZipError;
+    use crate::zip::write::FileOptions;
+    use std::io::{self, Read};
+
+    #[test]
+    fn build_in_memory() {
+        let options = FileOptions::default();
+        let archive = ZipBuilder::new()
+            .add_str("text.txt", "text", options.clone())
+            .add_bytes("bytes.bin", &[1, 2, 3], options.clone())
+            .add_reader("reader.txt", &b"reader"[..], options)
+            .build()
+            .unwrap();
+
+        let mut archive = ZipArchive::new(io::Cursor::new(archive)).unwrap();
+        let names: Vec<_> = (0..archive.len())
+            .map(|i| archive.by_index(i).unwrap().name().to_owned())
+            .collect();
+        assert_eq!(names, ["text.txt", "bytes.bin", "reader.txt"]);
+
+        let mut contents = Vec::new();
+        archive
+            .by_name("bytes.bin")
+            .unwrap()
+            .read_to_end(&mut contents)
+            .unwrap();
+        assert_eq!(contents, [1, 2, 3]);
+    }
+
+    struct FailingReader;
+
+    impl Read for FailingReader {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::BrokenPipe, "broken"))
+        }
+    }
+
+    #[test]
+    fn build_reports_reader_errors() {
+        let result = ZipBuilder::new()
+            .add_reader("file.txt", FailingReader, FileOptions::default())
+            .build();
+        match result {
+            Err(ZipError::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::BrokenPipe),
+            _ => panic!("the reader error should be returned"),
+        }
+    }
+}
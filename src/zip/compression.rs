@@ -2,6 +2,9 @@
 
 use std::fmt;
 
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
+
 #[allow(deprecated)]
 /// Identifies the storage format used to compress a file within a ZIP archive.
 ///
@@ -10,7 +13,8 @@ use std::fmt;
 ///
 /// When creating ZIP files, you may choose the method to use with
 /// [`crate::write::FileOptions::compression_method`]
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 #[non_exhaustive]
 pub enum CompressionMethod {
     /// Store the file as is
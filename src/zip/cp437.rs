@@ -35,6 +35,14 @@ impl FromCp437 for Vec<u8> {
     }
 }
 
+/// Encode a character in CP437, if it can be represented
+pub(crate) fn from_char(c: char) -> Option<u8> {
+    if c.is_ascii() {
+        return Some(c as u8);
+    }
+    (0x80..=0xff).find(|&byte| to_char(byte) == c)
+}
+
 fn to_char(input: u8) -> char {
     let output = match input {
         0x00..=0x7f => input as u32,
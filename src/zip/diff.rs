@@ -0,0 +1,304 @@
+//! Comparing the entries of two ZIP archives
+
+use crate::zip::compression::CompressionMethod;
+use crate::zip::manifest::{EncryptionKind, ManifestEntry};
+use crate::zip::read::ZipArchive;
+use crate::zip::result::ZipResult;
+use crate::zip::types::DateTime;
+use std::collections::HashMap;
+use std::io::{self, Read, Seek};
+
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
+
+/// Differences between two archives, as returned by [`diff`]
+///
+/// Entries are matched by name. When a name appears several times in an archive, the last
+/// entry is used, as with [`ZipArchive::by_name`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct ArchiveDiff {
+    /// Names of the entries only found in the new archive, in central directory order
+    pub added: Vec<String>,
+    /// Names of the entries only found in the old archive, in central directory order
+    pub removed: Vec<String>,
+    /// Entries found in both archives that differ, in the central directory order of the old
+    /// archive
+    pub modified: Vec<ModifiedEntry>,
+}
+
+impl ArchiveDiff {
+    /// Whether both archives have the same entries
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
+/// An entry found in both archives, whose contents or metadata changed
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct ModifiedEntry {
+    /// Name of the entry
+    pub name: String,
+    /// Whether the extracted contents differ
+    pub contents_changed: bool,
+    /// Changes of the metadata, which can change without the contents
+    pub metadata: Vec<MetadataChange>,
+}
+
+/// Change of the metadata of an entry, from the old value to the new one
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum MetadataChange {
+    /// The unix mode changed
+    UnixMode(Option<u32>, Option<u32>),
+    /// The last modified time changed
+    LastModified(DateTime, DateTime),
+    /// The compression method changed
+    CompressionMethod(CompressionMethod, CompressionMethod),
+}
+
+/// Compare two archives, looking at the CRC32 and size of the entries to detect changed contents
+///
+/// This only reads the central directory and local file headers, which is fast, but two files
+/// with the same size and CRC32 are assumed to be equal. Use [`diff_contents`] to compare the
+/// extracted data as well.
+pub fn diff<R, S>(old: &mut ZipArchive<R>, new: &mut ZipArchive<S>) -> ZipResult<ArchiveDiff>
+where
+    R: Read + Seek,
+    S: Read + Seek,
+{
+    compare(old, new, false)
+}
+
+/// Compare two archives like [`diff`], additionally comparing the extracted data of the entries
+/// that have the same size and CRC32
+///
+/// Encrypted entries cannot be extracted without their password, so only their size and CRC32
+/// are compared.
+pub fn diff_contents<R, S>(
+    old: &mut ZipArchive<R>,
+    new: &mut ZipArchive<S>,
+) -> ZipResult<ArchiveDiff>
+where
+    R: Read + Seek,
+    S: Read + Seek,
+{
+    compare(old, new, true)
+}
+
+fn compare<R, S>(
+    old: &mut ZipArchive<R>,
+    new: &mut ZipArchive<S>,
+    read_contents: bool,
+) -> ZipResult<ArchiveDiff>
+where
+    R: Read + Seek,
+    S: Read + Seek,
+{
+    let old_entries = old.manifest()?.entries;
+    let new_entries = new.manifest()?.entries;
+    let old_index = index_by_name(&old_entries);
+    let new_index = index_by_name(&new_entries);
+
+    let mut diff = ArchiveDiff::default();
+    for (i, entry) in new_entries.iter().enumerate() {
+        if !old_index.contains_key(entry.name.as_str()) && new_index[entry.name.as_str()] == i {
+            diff.added.push(entry.name.clone());
+        }
+    }
+
+    for (i, old_entry) in old_entries.iter().enumerate() {
+        if old_index[old_entry.name.as_str()] != i {
+            continue;
+        }
+        let j = match new_index.get(old_entry.name.as_str()) {
+            Some(&j) => j,
+            None => {
+                diff.removed.push(old_entry.name.clone());
+                continue;
+            }
+        };
+        let new_entry = &new_entries[j];
+
+        let mut contents_changed =
+            old_entry.crc32 != new_entry.crc32 || old_entry.size != new_entry.size;
+        if !contents_changed
+            && read_contents
+            && old_entry.encryption == EncryptionKind::None
+            && new_entry.encryption == EncryptionKind::None
+        {
+            contents_changed = !same_contents(&mut old.by_index(i)?, &mut new.by_index(j)?)?;
+        }
+
+        let metadata = metadata_changes(old_entry, new_entry);
+        if contents_changed || !metadata.is_empty() {
+            diff.modified.push(ModifiedEntry {
+                name: old_entry.name.clone(),
+                contents_changed,
+                metadata,
+            });
+        }
+    }
+
+    Ok(diff)
+}
+
+/// Map the names to the index of their last entry.
+fn index_by_name(entries: &[ManifestEntry]) -> HashMap<&str, usize> {
+    entries
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| (entry.name.as_str(), i))
+        .collect()
+}
+
+fn metadata_changes(old: &ManifestEntry, new: &ManifestEntry) -> Vec<MetadataChange> {
+    let mut changes = Vec::new();
+    if old.unix_mode != new.unix_mode {
+        changes.push(MetadataChange::UnixMode(old.unix_mode, new.unix_mode));
+    }
+    if old.last_modified != new.last_modified {
+        changes.push(MetadataChange::LastModified(
+            old.last_modified,
+            new.last_modified,
+        ));
+    }
+    if old.compression_method != new.compression_method {
+        changes.push(MetadataChange::CompressionMethod(
+            old.compression_method,
+            new.compression_method,
+        ));
+    }
+    changes
+}
+
+fn same_contents(a: &mut impl Read, b: &mut impl Read) -> io::Result<bool> {
+    let mut buf_a = [0; 8192];
+    let mut buf_b = [0; 8192];
+    loop {
+        let len_a = fill(a, &mut buf_a)?;
+        let len_b = fill(b, &mut buf_b)?;
+        if buf_a[..len_a] != buf_b[..len_b] {
+            return Ok(false);
+        }
+        if len_a == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+/// Read until `buf` is full or the end of the reader is reached, returning the number of bytes
+/// read.
+fn fill(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{diff, diff_contents, MetadataChange, ModifiedEntry};
+    use crate::zip::compression::CompressionMethod;
+    use crate::zip::read::ZipArchive;
+    use crate::zip::types::DateTime;
+    use crate::zip::write::{FileOptions, ZipWriter};
+    use std::io::{self, Write};
+
+    fn archive(files: &[(&str, &[u8], FileOptions)]) -> ZipArchive<io::Cursor<Vec<u8>>> {
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        for (name, contents, options) in files {
+            writer.start_file(*name, options.clone()).unwrap();
+            writer.write_all(contents).unwrap();
+        }
+        ZipArchive::new(writer.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn diff_entries() {
+        let old_time = DateTime::from_date_and_time(2023, 1, 1, 0, 0, 0).unwrap();
+        let new_time = DateTime::from_date_and_time(2024, 1, 1, 0, 0, 0).unwrap();
+        let options = FileOptions::default()
+            .compression_method(CompressionMethod::Stored)
+            .last_modified_time(old_time);
+        let touched = options
+            .clone()
+            .last_modified_time(new_time)
+            .unix_permissions(0o755);
+
+        let mut old = archive(&[
+            ("same.txt", b"same", options.clone()),
+            ("removed.txt", b"removed", options.clone()),
+            ("changed.txt", b"old", options.clone()),
+            ("touched.txt", b"touched", options.clone()),
+        ]);
+        let mut new = archive(&[
+            ("touched.txt", b"touched", touched),
+            ("changed.txt", b"new contents", options.clone()),
+            ("added.txt", b"added", options.clone()),
+            ("same.txt", b"same", options),
+        ]);
+
+        let diff = diff(&mut old, &mut new).unwrap();
+        assert_eq!(diff.added, ["added.txt"]);
+        assert_eq!(diff.removed, ["removed.txt"]);
+        assert_eq!(
+            diff.modified,
+            [
+                ModifiedEntry {
+                    name: "changed.txt".to_owned(),
+                    contents_changed: true,
+                    metadata: Vec::new(),
+                },
+                ModifiedEntry {
+                    name: "touched.txt".to_owned(),
+                    contents_changed: false,
+                    metadata: vec![
+                        MetadataChange::UnixMode(Some(0o100644), Some(0o100755)),
+                        MetadataChange::LastModified(old_time, new_time),
+                    ],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_identical() {
+        let options = FileOptions::default()
+            .last_modified_time(DateTime::from_date_and_time(2023, 1, 1, 0, 0, 0).unwrap());
+        let mut old = archive(&[("file.txt", b"contents", options.clone())]);
+        let mut new = archive(&[("file.txt", b"contents", options)]);
+        assert!(diff_contents(&mut old, &mut new).unwrap().is_empty());
+    }
+
+    #[test]
+    fn diff_same_crc32() {
+        let options = FileOptions::default()
+            .compression_method(CompressionMethod::Stored)
+            .last_modified_time(DateTime::from_date_and_time(2023, 1, 1, 0, 0, 0).unwrap());
+        // Different contents of the same size and CRC32
+        let mut old = archive(&[("file.txt", b"plumless", options.clone())]);
+        let mut new = archive(&[("file.txt", b"buckeroo", options)]);
+        assert_eq!(
+            old.by_index(0).unwrap().crc32(),
+            new.by_index(0).unwrap().crc32()
+        );
+
+        assert!(diff(&mut old, &mut new).unwrap().is_empty());
+        assert_eq!(
+            diff_contents(&mut old, &mut new).unwrap().modified,
+            [ModifiedEntry {
+                name: "file.txt".to_owned(),
+                contents_changed: true,
+                metadata: Vec::new(),
+            }]
+        );
+    }
+}
@@ -0,0 +1,266 @@
+//! Legacy encodings of the file names and comments that are not marked as UTF-8
+
+use crate::zip::cp437::{self, FromCp437};
+use std::borrow::Cow;
+
+/// Encoding of the file names and comments that are not marked as UTF-8
+///
+/// The ZIP specification only defines IBM code page 437 for these, but many archivers use
+/// the code page of the system instead. Use it with [`crate::zip::read::ZipArchive::with_name_encoding`],
+/// [`crate::zip::read::LazyZipArchive::with_name_encoding`],
+/// [`crate::zip::read::read_zipfile_from_stream_with_name_encoding`] and
+/// [`crate::zip::write::ZipWriter::set_name_encoding`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Encoding {
+    /// UTF-8, used by archivers that don't set the UTF-8 flag
+    Utf8,
+    /// IBM code page 437, as defined by the ZIP specification
+    Cp437,
+    /// IBM code page 866, used by DOS and Windows archivers in Russia
+    Cp866,
+    /// Shift_JIS, used by Windows archivers in Japan
+    ShiftJis,
+    /// GBK, used by Windows archivers in China
+    Gbk,
+    /// Detected from the file names of the whole archive with [`Encoding::detect`]
+    ///
+    /// This is the same as `Utf8` when writing.
+    Auto,
+}
+
+impl Encoding {
+    /// Guess the encoding of raw file names, which should come from the same archive
+    ///
+    /// Names that are valid UTF-8 are assumed to be UTF-8. The other names are tried with the
+    /// multi-byte encodings, preferring the one giving the fewest characters that are unlikely
+    /// in file names, such as half-width katakana for Shift_JIS. Names that are not valid in
+    /// any multi-byte encoding are decoded as CP866 when all their non-ASCII bytes are
+    /// Cyrillic letters in CP866 and no Cyrillic letter is next to a Latin letter, and as
+    /// CP437 otherwise.
+    pub fn detect<'a, I>(names: I) -> Encoding
+    where
+        I: IntoIterator<Item = &'a [u8]>,
+    {
+        let mut non_ascii = false;
+        let mut legacy = Vec::new();
+        for name in names {
+            if name.is_ascii() {
+                continue;
+            }
+            non_ascii = true;
+            if std::str::from_utf8(name).is_err() {
+                legacy.push(name);
+            }
+        }
+        if legacy.is_empty() {
+            return if non_ascii {
+                Encoding::Utf8
+            } else {
+                Encoding::Cp437
+            };
+        }
+
+        let multi_byte = [Encoding::ShiftJis, Encoding::Gbk]
+            .iter()
+            .filter_map(|&encoding| Some((encoding, encoding.unlikely_characters(&legacy)?)))
+            .min_by_key(|&(_, score)| score);
+        if let Some((encoding, _)) = multi_byte {
+            return encoding;
+        }
+
+        if legacy.iter().all(|name| is_cp866_cyrillic(name)) {
+            Encoding::Cp866
+        } else {
+            Encoding::Cp437
+        }
+    }
+
+    /// The encoding of a single name, detected from the name alone with `Auto`
+    pub(crate) fn for_name(self, name: &[u8]) -> Encoding {
+        match self {
+            Encoding::Auto => Encoding::detect(std::iter::once(name)),
+            encoding => encoding,
+        }
+    }
+
+    /// Count the characters unlikely in file names when decoding `names`, or `None` if some
+    /// of them are not valid in this encoding.
+    fn unlikely_characters(self, names: &[&[u8]]) -> Option<usize> {
+        let encoding = self.legacy()?;
+        let mut count = 0;
+        for name in names {
+            let decoded = encoding.decode_without_bom_handling_and_without_replacement(name)?;
+            count += decoded
+                .chars()
+                .filter(|&c| {
+                    c.is_control() || matches!(c, '\u{e000}'..='\u{f8ff}' | '\u{ff61}'..='\u{ff9f}')
+                })
+                .count();
+        }
+        Some(count)
+    }
+
+    /// The encoding implemented by `encoding_rs`, if any
+    fn legacy(self) -> Option<&'static encoding_rs::Encoding> {
+        match self {
+            Encoding::Cp866 => Some(encoding_rs::IBM866),
+            Encoding::ShiftJis => Some(encoding_rs::SHIFT_JIS),
+            Encoding::Gbk => Some(encoding_rs::GBK),
+            Encoding::Utf8 | Encoding::Cp437 | Encoding::Auto => None,
+        }
+    }
+
+    /// Decode a file name or comment, replacing invalid sequences. `Auto` decodes CP437, as
+    /// the encoding has to be detected beforehand.
+    pub(crate) fn decode(self, bytes: &[u8]) -> Cow<'_, str> {
+        match self.legacy() {
+            Some(encoding) => encoding.decode_without_bom_handling(bytes).0,
+            None if self == Encoding::Utf8 => String::from_utf8_lossy(bytes),
+            None => bytes.from_cp437(),
+        }
+    }
+
+    /// Encode a file name or comment, or `None` if some characters cannot be represented.
+    /// `Auto` encodes UTF-8.
+    pub(crate) fn encode(self, text: &str) -> Option<Cow<'_, [u8]>> {
+        match self.legacy() {
+            Some(encoding) => match encoding.encode(text) {
+                (_, _, true) => None,
+                (bytes, _, false) => Some(bytes),
+            },
+            None if self == Encoding::Cp437 => text
+                .chars()
+                .map(cp437::from_char)
+                .collect::<Option<Vec<u8>>>()
+                .map(Cow::Owned),
+            None => Some(text.as_bytes().into()),
+        }
+    }
+}
+
+/// Whether `name` looks like Cyrillic text in CP866, rather than accented Latin text in CP437
+fn is_cp866_cyrillic(name: &[u8]) -> bool {
+    if !name
+        .iter()
+        .all(|byte| byte.is_ascii() || matches!(byte, 0x80..=0xaf | 0xe0..=0xf1))
+    {
+        return false;
+    }
+    let decoded: Vec<char> = Encoding::Cp866.decode(name).chars().collect();
+    decoded.windows(2).all(|pair| {
+        let cyrillic = |c: char| ('\u{400}'..='\u{4ff}').contains(&c);
+        !(cyrillic(pair[0]) && pair[1].is_ascii_alphabetic()
+            || pair[0].is_ascii_alphabetic() && cyrillic(pair[1]))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::Encoding;
+    use crate::zip::manifest::EncryptionKind;
+    use crate::zip::read::{
+        read_zipfile_from_stream_with_name_encoding, LazyZipArchive, ZipArchive,
+    };
+    use crate::zip::write::{FileOptions, ZipWriter};
+    use std::io;
+
+    const NAMES: [(Encoding, &str); 5] = [
+        (Encoding::Cp437, "Café.txt"),
+        (Encoding::Cp866, "Отчёт.txt"),
+        (Encoding::ShiftJis, "日本語のファイル.txt"),
+        (Encoding::Gbk, "中文文件名.txt"),
+        (Encoding::Utf8, "Ünïcødé.txt"),
+    ];
+
+    #[test]
+    fn encode_decode_detect() {
+        for &(encoding, name) in NAMES.iter() {
+            let encoded = encoding.encode(name).unwrap();
+            assert_eq!(encoding.decode(&encoded), name);
+            assert_eq!(Encoding::detect(vec![&encoded[..], b"plain.txt"]), encoding);
+        }
+        assert_eq!(Encoding::ShiftJis.encode("한국어"), None);
+        assert_eq!(Encoding::detect(vec![&b"plain.txt"[..]]), Encoding::Cp437);
+    }
+
+    #[test]
+    fn legacy_names_roundtrip() {
+        for &(encoding, name) in NAMES.iter() {
+            let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+            writer.set_name_encoding(encoding);
+            writer.start_file(name, FileOptions::default()).unwrap();
+            writer
+                .start_file("plain.txt", FileOptions::default())
+                .unwrap();
+            let bytes = writer.finish().unwrap().into_inner();
+
+            let flags = u16::from_le_bytes([bytes[6], bytes[7]]);
+            assert_eq!(flags & (1 << 11) != 0, encoding == Encoding::Utf8);
+
+            let archive =
+                ZipArchive::with_name_encoding(io::Cursor::new(&bytes), encoding).unwrap();
+            assert!(archive.file_names().any(|n| n == name));
+            let archive =
+                ZipArchive::with_name_encoding(io::Cursor::new(&bytes), Encoding::Auto).unwrap();
+            assert!(archive.file_names().any(|n| n == name));
+        }
+
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        writer.set_name_encoding(Encoding::Cp866);
+        assert!(writer.start_file("日本語", FileOptions::default()).is_err());
+    }
+
+    #[test]
+    fn legacy_names_append() {
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        writer.set_name_encoding(Encoding::Cp437);
+        writer
+            .start_file("Café.txt", FileOptions::default().comment("Ubersicht"))
+            .unwrap();
+        let mut bytes = writer.finish().unwrap().into_inner();
+        // Older archivers also encode the comments with the code page, which can't be written
+        let comment = bytes.windows(9).position(|w| w == b"Ubersicht").unwrap();
+        bytes[comment] = 0x9a;
+
+        let mut writer = ZipWriter::new_append(io::Cursor::new(bytes)).unwrap();
+        writer
+            .start_file("plain.txt", FileOptions::default())
+            .unwrap();
+        let bytes = writer.finish().unwrap().into_inner();
+
+        let mut archive =
+            ZipArchive::with_name_encoding(io::Cursor::new(&bytes), Encoding::Cp437).unwrap();
+        let file = archive.by_index(0).unwrap();
+        assert_eq!(file.name(), "Café.txt");
+        assert_eq!(file.comment(), "Übersicht");
+    }
+
+    #[test]
+    fn legacy_names_lazy_and_stream() {
+        for &(encoding, name) in NAMES.iter() {
+            let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+            writer.set_name_encoding(encoding);
+            writer.start_file(name, FileOptions::default()).unwrap();
+            let bytes = writer.finish().unwrap().into_inner();
+
+            for &read_encoding in [encoding, Encoding::Auto].iter() {
+                let mut lazy =
+                    LazyZipArchive::with_name_encoding(io::Cursor::new(&bytes), read_encoding)
+                        .unwrap();
+                assert_eq!(lazy.name_for_index(0).unwrap(), name);
+                assert_eq!(lazy.by_name(name).unwrap().name(), name);
+
+                let mut reader = io::Cursor::new(&bytes);
+                let file = read_zipfile_from_stream_with_name_encoding(
+                    &mut reader,
+                    &mut |_: &str, _: EncryptionKind| None,
+                    read_encoding,
+                )
+                .unwrap()
+                .unwrap();
+                assert_eq!(file.name(), name);
+            }
+        }
+    }
+}
@@ -0,0 +1,166 @@
+//! A serializable description of the contents of a ZIP archive.
+
+use crate::zip::compression::CompressionMethod;
+use crate::zip::types::{AesMode, AesVendorVersion, DateTime, ZipFileData};
+
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
+
+/// Summary of the contents of an archive, as returned by [`crate::zip::read::ZipArchive::manifest`]
+///
+/// With the `serialize` feature enabled, it can be exported with any serde format to audit
+/// archives without opening them again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct Manifest {
+    /// Size of the data prepended to the archive, see [`crate::zip::read::ZipArchive::offset`]
+    pub offset: u64,
+    /// Raw archive comment
+    pub comment: Vec<u8>,
+    /// Entries in central directory order
+    pub entries: Vec<ManifestEntry>,
+}
+
+/// Metadata of a single entry in a [`Manifest`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct ManifestEntry {
+    /// Decoded name of the file
+    pub name: String,
+    /// Name of the file as stored in the archive
+    pub name_raw: Vec<u8>,
+    /// Comment of the file
+    pub comment: String,
+    /// Size of the file in the archive
+    pub compressed_size: u64,
+    /// Size of the file when extracted
+    pub size: u64,
+    /// Compression method used to store the file
+    pub compression_method: CompressionMethod,
+    /// CRC32 checksum of the extracted file
+    pub crc32: u32,
+    /// Last modified time, with a 2 second precision
+    pub last_modified: DateTime,
+    /// Unix mode of the file, if known
+    pub unix_mode: Option<u32>,
+    /// Encryption used to protect the file
+    pub encryption: EncryptionKind,
+    /// Offset of the local file header
+    pub header_start: u64,
+    /// Offset of the file header in the central directory
+    pub central_header_start: u64,
+    /// Offset of the file data
+    pub data_start: u64,
+}
+
+/// Encryption used for a file in an archive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum EncryptionKind {
+    /// The file is not encrypted
+    None,
+    /// The file uses the legacy ZipCrypto algorithm
+    ZipCrypto,
+    /// The file is encrypted with AES
+    Aes {
+        /// Key size
+        mode: AesMode,
+        /// WinZip AE-x version
+        vendor_version: AesVendorVersion,
+    },
+}
+
+impl EncryptionKind {
+    pub(crate) fn of(data: &ZipFileData) -> EncryptionKind {
+        match (data.encrypted, data.aes_mode) {
+            (_, Some((mode, vendor_version))) => EncryptionKind::Aes {
+                mode,
+                vendor_version,
+            },
+            (true, None) => EncryptionKind::ZipCrypto,
+            (false, None) => EncryptionKind::None,
+        }
+    }
+}
+
+impl ManifestEntry {
+    /// Describe `data`, whose file data was found to start at `data_start`.
+    pub(crate) fn new(data: &ZipFileData, data_start: u64) -> ManifestEntry {
+        ManifestEntry {
+            name: data.file_name.clone(),
+            name_raw: data.file_name_raw.clone(),
+            comment: data.file_comment.clone(),
+            compressed_size: data.compressed_size,
+            size: data.uncompressed_size,
+            compression_method: data.compression_method,
+            crc32: data.crc32,
+            last_modified: data.last_modified_time,
+            unix_mode: data.unix_mode(),
+            encryption: EncryptionKind::of(data),
+            header_start: data.header_start,
+            central_header_start: data.central_header_start,
+            data_start,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::EncryptionKind;
+    use crate::zip::compression::CompressionMethod;
+    use crate::zip::read::ZipArchive;
+    use crate::zip::types::DateTime;
+    use crate::zip::write::{FileOptions, ZipWriter};
+    use std::io::{self, Write};
+
+    fn sample_archive() -> ZipArchive<io::Cursor<Vec<u8>>> {
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        let options = FileOptions::default()
+            .compression_method(CompressionMethod::Stored)
+            .last_modified_time(DateTime::from_date_and_time(2023, 9, 1, 12, 30, 0).unwrap())
+            .unix_permissions(0o600);
+        writer.start_file("plain.txt", options.clone()).unwrap();
+        writer.write_all(b"plain").unwrap();
+        writer
+            .start_file(
+                "secret.txt",
+                options.with_deprecated_encryption(b"password"),
+            )
+            .unwrap();
+        writer.write_all(b"secret").unwrap();
+        writer.set_comment("manifest");
+        ZipArchive::new(writer.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn manifest_entries() {
+        let mut archive = sample_archive();
+        let manifest = archive.manifest().unwrap();
+        assert_eq!(manifest.comment, b"manifest");
+        assert_eq!(manifest.entries.len(), 2);
+
+        let plain = &manifest.entries[0];
+        assert_eq!(plain.name, "plain.txt");
+        assert_eq!(plain.size, 5);
+        assert_eq!(plain.unix_mode, Some(0o100600));
+        assert_eq!(plain.encryption, EncryptionKind::None);
+        assert_eq!(plain.data_start, 30 + "plain.txt".len() as u64);
+        assert_eq!(plain.last_modified.year(), 2023);
+
+        let secret = &manifest.entries[1];
+        assert_eq!(secret.encryption, EncryptionKind::ZipCrypto);
+        assert_eq!(secret.compressed_size, 12 + 6);
+        assert!(secret.header_start > plain.data_start);
+    }
+
+    #[cfg(feature = "serialize")]
+    #[test]
+    fn manifest_json_round_trip() {
+        use super::Manifest;
+
+        let manifest = sample_archive().manifest().unwrap();
+        let serialized = serde_json::to_string(&manifest).unwrap();
+        let deserialized: Manifest = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(manifest, deserialized);
+    }
+}
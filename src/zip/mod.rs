@@ -2,9 +2,19 @@ mod aes;
 mod aes_ctr;
 mod cp437;
 mod crc32;
+#[cfg(feature = "signing")]
+mod signature;
 mod spec;
 mod zipcrypto;
+mod zstd_dictionary;
+pub mod builder;
 pub mod compression;
+pub mod diff;
+pub mod encoding;
+pub mod manifest;
+pub mod password;
+pub mod progress;
+pub mod range;
 pub mod write;
 pub mod read;
 pub mod result;
@@ -0,0 +1,35 @@
+//! Passwords for encrypted files
+
+use crate::zip::manifest::EncryptionKind;
+use std::collections::HashMap;
+use zeroize::Zeroizing;
+
+/// A password, which is wiped from memory when dropped
+pub type Password = Zeroizing<Vec<u8>>;
+
+/// Supplies the password of each encrypted file in an archive
+///
+/// This allows processing archives whose files are encrypted with different passwords in a
+/// single pass, see [`crate::zip::read::ZipArchive::by_index_with_passwords`]. The returned
+/// password is dropped, and thus wiped, as soon as the decryption keys are derived from it.
+///
+/// The trait is implemented for closures, and for maps from file names to passwords.
+pub trait PasswordProvider {
+    /// Returns the password of the file `name`, or `None` if it is not known.
+    fn password(&mut self, name: &str, encryption: EncryptionKind) -> Option<Password>;
+}
+
+impl<F> PasswordProvider for F
+where
+    F: FnMut(&str, EncryptionKind) -> Option<Password>,
+{
+    fn password(&mut self, name: &str, encryption: EncryptionKind) -> Option<Password> {
+        self(name, encryption)
+    }
+}
+
+impl PasswordProvider for HashMap<String, Password> {
+    fn password(&mut self, name: &str, _: EncryptionKind) -> Option<Password> {
+        self.get(name).cloned()
+    }
+}
@@ -0,0 +1,255 @@
+//! Progress reporting and cancellation for long operations
+
+use crate::zip::result::{ZipError, ZipResult};
+use std::io::{self, Read, Write};
+
+/// Whether an operation should go on, as returned by the methods of [`Progress`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+    /// Go on with the operation
+    Continue,
+    /// Stop the operation, which then fails with [`ZipError::Cancelled`]
+    Cancel,
+}
+
+/// Receives updates while entries are extracted, archived or copied
+///
+/// Both methods do nothing by default, so implementations only need to override the updates
+/// they are interested in. The unit type `()` implements this trait without reporting
+/// anything.
+///
+/// This is accepted by [`crate::zip::read::ZipArchive::extract_with_progress`],
+/// [`crate::zip::write::ZipWriter::add_path_recursive_with_progress`] and
+/// [`crate::zip::write::ZipWriter::raw_copy_file_rename_with_progress`].
+pub trait Progress {
+    /// Called before an entry is processed, with the number of bytes that will be processed
+    /// for it if known
+    fn start_entry(&mut self, name: &str, size: Option<u64>) -> Control {
+        let _ = (name, size);
+        Control::Continue
+    }
+
+    /// Called while the data of the current entry is processed, with the number of bytes
+    /// processed since the last call
+    fn advance(&mut self, bytes: u64) -> Control {
+        let _ = bytes;
+        Control::Continue
+    }
+}
+
+impl Progress for () {}
+
+/// Call [`Progress::start_entry`], failing if the operation is cancelled.
+pub(crate) fn start_entry(
+    progress: &mut dyn Progress,
+    name: &str,
+    size: Option<u64>,
+) -> ZipResult<()> {
+    match progress.start_entry(name, size) {
+        Control::Continue => Ok(()),
+        Control::Cancel => Err(ZipError::Cancelled),
+    }
+}
+
+/// Copy `reader` to `writer` like [`io::copy`], reporting every chunk to `progress`.
+pub(crate) fn copy<R, W>(
+    reader: &mut R,
+    writer: &mut W,
+    progress: &mut dyn Progress,
+) -> ZipResult<u64>
+where
+    R: Read + ?Sized,
+    W: Write + ?Sized,
+{
+    let mut buf = [0; 64 * 1024];
+    let mut copied = 0;
+    loop {
+        let len = match reader.read(&mut buf) {
+            Ok(0) => return Ok(copied),
+            Ok(len) => len,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        writer.write_all(&buf[..len])?;
+        copied += len as u64;
+        if progress.advance(len as u64) == Control::Cancel {
+            return Err(ZipError::Cancelled);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Control, Progress};
+    use crate::zip::read::ZipArchive;
+    use crate::zip::result::ZipError;
+    use crate::zip::write::{FileOptions, ZipWriter};
+    use std::io::{self, Write};
+
+    /// Records the updates, and cancels once `limit` entries were started or more than
+    /// `byte_limit` bytes were processed
+    #[derive(Default)]
+    struct Recorder {
+        entries: Vec<(String, Option<u64>)>,
+        bytes: u64,
+        limit: Option<usize>,
+        byte_limit: Option<u64>,
+    }
+
+    impl Progress for Recorder {
+        fn start_entry(&mut self, name: &str, size: Option<u64>) -> Control {
+            if Some(self.entries.len()) == self.limit {
+                return Control::Cancel;
+            }
+            self.entries.push((name.to_owned(), size));
+            Control::Continue
+        }
+
+        fn advance(&mut self, bytes: u64) -> Control {
+            self.bytes += bytes;
+            match self.byte_limit {
+                Some(limit) if self.bytes > limit => Control::Cancel,
+                _ => Control::Continue,
+            }
+        }
+    }
+
+    fn sample_archive() -> ZipArchive<io::Cursor<Vec<u8>>> {
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        writer.add_directory("dir", FileOptions::default()).unwrap();
+        writer
+            .start_file("dir/a.txt", FileOptions::default())
+            .unwrap();
+        writer.write_all(&[b'a'; 100_000]).unwrap();
+        writer
+            .start_file("dir/b.txt", FileOptions::default())
+            .unwrap();
+        writer.write_all(b"b").unwrap();
+        ZipArchive::new(writer.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn extract_progress() {
+        let directory = std::env::temp_dir().join(format!("zip_progress_{}", std::process::id()));
+        let mut archive = sample_archive();
+
+        let mut progress = Recorder::default();
+        archive
+            .extract_with_progress(&directory, &mut |_: &str, _| None, &mut progress)
+            .unwrap();
+        assert_eq!(
+            progress.entries,
+            [
+                ("dir/".to_owned(), Some(0)),
+                ("dir/a.txt".to_owned(), Some(100_000)),
+                ("dir/b.txt".to_owned(), Some(1)),
+            ]
+        );
+        assert_eq!(progress.bytes, 100_001);
+
+        std::fs::remove_dir_all(&directory).unwrap();
+        let mut progress = Recorder {
+            limit: Some(1),
+            ..Recorder::default()
+        };
+        let result =
+            archive.extract_with_progress(&directory, &mut |_: &str, _| None, &mut progress);
+        assert!(matches!(result, Err(ZipError::Cancelled)));
+        assert!(directory.join("dir").exists());
+        assert!(!directory.join("dir/a.txt").exists());
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn archive_directory_progress() {
+        let directory =
+            std::env::temp_dir().join(format!("zip_progress_dir_{}", std::process::id()));
+        sample_archive().extract(&directory).unwrap();
+
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        let mut progress = Recorder::default();
+        writer
+            .add_path_recursive_with_progress(
+                directory.join("dir"),
+                "copy",
+                FileOptions::default(),
+                &mut progress,
+            )
+            .unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(
+            progress.entries,
+            [
+                ("copy/".to_owned(), Some(0)),
+                ("copy/a.txt".to_owned(), Some(100_000)),
+                ("copy/b.txt".to_owned(), Some(1)),
+            ]
+        );
+        assert_eq!(progress.bytes, 100_001);
+
+        let archive = ZipArchive::new(writer.finish().unwrap()).unwrap();
+        let names: Vec<_> = archive.file_names().collect();
+        assert_eq!(names.len(), 3);
+    }
+
+    #[test]
+    fn archive_directory_cancel() {
+        let directory =
+            std::env::temp_dir().join(format!("zip_progress_cancel_{}", std::process::id()));
+        sample_archive().extract(&directory).unwrap();
+
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        writer
+            .start_file("first.txt", FileOptions::default())
+            .unwrap();
+        writer.write_all(b"first").unwrap();
+        let mut progress = Recorder {
+            byte_limit: Some(1000),
+            ..Recorder::default()
+        };
+        let result = writer.add_path_recursive_with_progress(
+            directory.join("dir"),
+            "copy",
+            FileOptions::default(),
+            &mut progress,
+        );
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert!(matches!(result, Err(ZipError::Cancelled)));
+
+        // The file being written when cancelling is dropped
+        let mut archive = ZipArchive::new(writer.finish().unwrap()).unwrap();
+        let mut names: Vec<_> = archive.file_names().collect();
+        names.sort_unstable();
+        assert_eq!(names, ["copy/", "first.txt"]);
+        assert!(archive.by_name("first.txt").unwrap().size() == 5);
+    }
+
+    #[test]
+    fn raw_copy_cancel() {
+        let mut source = sample_archive();
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+
+        let mut progress = Recorder::default();
+        let file = source.by_name("dir/a.txt").unwrap();
+        let compressed_size = file.compressed_size();
+        writer
+            .raw_copy_file_rename_with_progress(file, "a.txt", &mut progress)
+            .unwrap();
+        assert_eq!(
+            progress.entries,
+            [("a.txt".to_owned(), Some(compressed_size))]
+        );
+        assert_eq!(progress.bytes, compressed_size);
+
+        let mut progress = Recorder {
+            limit: Some(0),
+            ..Recorder::default()
+        };
+        let file = source.by_name("dir/b.txt").unwrap();
+        let result = writer.raw_copy_file_rename_with_progress(file, "b.txt", &mut progress);
+        assert!(matches!(result, Err(ZipError::Cancelled)));
+
+        let archive = ZipArchive::new(writer.finish().unwrap()).unwrap();
+        assert_eq!(archive.len(), 1);
+    }
+}
@@ -0,0 +1,260 @@
+//! Reading archives through random access sources, such as HTTP range requests
+
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom};
+
+/// A source of bytes that can only be read by ranges, such as an object store or an HTTP
+/// server supporting range requests
+///
+/// Wrap it in a [`RangeReader`] to open it with [`crate::zip::read::ZipArchive::new`].
+pub trait RangeSource {
+    /// Total size of the source, in bytes
+    fn size(&mut self) -> io::Result<u64>;
+
+    /// Read `len` bytes starting at `offset`
+    ///
+    /// The range is always within the size of the source. Returning fewer bytes than
+    /// requested is an error.
+    fn read_at(&mut self, offset: u64, len: usize) -> io::Result<Vec<u8>>;
+}
+
+impl RangeSource for fs::File {
+    fn size(&mut self) -> io::Result<u64> {
+        Ok(self.metadata()?.len())
+    }
+
+    fn read_at(&mut self, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        let mut buffer = vec![0; len];
+        self.seek(SeekFrom::Start(offset))?;
+        self.read_exact(&mut buffer)?;
+        Ok(buffer)
+    }
+}
+
+/// An adapter implementing `Read + Seek` over a [`RangeSource`], caching the blocks it reads
+///
+/// Reads are rounded to whole blocks, and consecutive missing blocks are fetched with a
+/// single request, so opening an archive only takes a few requests for the end of central
+/// directory record and the central directory. The least recently used blocks are evicted
+/// once the cache is full.
+pub struct RangeReader<S> {
+    source: S,
+    size: u64,
+    pos: u64,
+    block_size: u64,
+    max_blocks: usize,
+    blocks: HashMap<u64, Vec<u8>>,
+    /// Cached block numbers, from least to most recently used
+    order: VecDeque<u64>,
+    requests: u64,
+    bytes_fetched: u64,
+}
+
+impl<S: RangeSource> RangeReader<S> {
+    /// Default size of the cached blocks
+    pub const DEFAULT_BLOCK_SIZE: usize = 64 * 1024;
+    /// Default number of cached blocks
+    pub const DEFAULT_MAX_BLOCKS: usize = 64;
+
+    /// Wrap `source`, caching up to 64 blocks of 64 KiB
+    pub fn new(source: S) -> io::Result<Self> {
+        Self::with_cache(source, Self::DEFAULT_BLOCK_SIZE, Self::DEFAULT_MAX_BLOCKS)
+    }
+
+    /// Wrap `source`, caching up to `max_blocks` blocks of `block_size` bytes
+    pub fn with_cache(mut source: S, block_size: usize, max_blocks: usize) -> io::Result<Self> {
+        if block_size == 0 || max_blocks == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "The block size and the number of blocks must not be zero",
+            ));
+        }
+        let size = source.size()?;
+        Ok(RangeReader {
+            source,
+            size,
+            pos: 0,
+            block_size: block_size as u64,
+            max_blocks,
+            blocks: HashMap::new(),
+            order: VecDeque::new(),
+            requests: 0,
+            bytes_fetched: 0,
+        })
+    }
+
+    /// Number of requests made to the source so far
+    pub fn requests(&self) -> u64 {
+        self.requests
+    }
+
+    /// Number of bytes read from the source so far
+    pub fn bytes_fetched(&self) -> u64 {
+        self.bytes_fetched
+    }
+
+    /// Unwrap and return the source
+    pub fn into_inner(self) -> S {
+        self.source
+    }
+
+    /// Fetch the missing blocks from `first` up to `last`, in a single request
+    fn fetch(&mut self, first: u64, last: u64) -> io::Result<()> {
+        let mut last = last.min(first + self.max_blocks as u64 - 1);
+        if let Some(cached) = (first..=last).find(|block| self.blocks.contains_key(block)) {
+            last = cached - 1;
+        }
+        let start = first * self.block_size;
+        let end = ((last + 1) * self.block_size).min(self.size);
+        let data = self.source.read_at(start, (end - start) as usize)?;
+        if data.len() as u64 != end - start {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "The range source returned fewer bytes than requested",
+            ));
+        }
+        self.requests += 1;
+        self.bytes_fetched += data.len() as u64;
+
+        for (block, chunk) in (first..).zip(data.chunks(self.block_size as usize)) {
+            if self.blocks.len() == self.max_blocks {
+                if let Some(evicted) = self.order.pop_front() {
+                    self.blocks.remove(&evicted);
+                }
+            }
+            self.blocks.insert(block, chunk.to_vec());
+            self.order.push_back(block);
+        }
+        Ok(())
+    }
+
+    fn touch(&mut self, block: u64) {
+        if let Some(index) = self.order.iter().position(|&cached| cached == block) {
+            self.order.remove(index);
+            self.order.push_back(block);
+        }
+    }
+}
+
+impl<S: RangeSource> Read for RangeReader<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.size || buf.is_empty() {
+            return Ok(0);
+        }
+        let end = (self.pos + buf.len() as u64).min(self.size);
+        let block = self.pos / self.block_size;
+        if !self.blocks.contains_key(&block) {
+            self.fetch(block, (end - 1) / self.block_size)?;
+        } else {
+            self.touch(block);
+        }
+
+        let data = &self.blocks[&block];
+        let offset = (self.pos - block * self.block_size) as usize;
+        let count = (data.len() - offset).min((end - self.pos) as usize);
+        buf[..count].copy_from_slice(&data[offset..offset + count]);
+        self.pos += count as u64;
+        Ok(count)
+    }
+}
+
+impl<S: RangeSource> Seek for RangeReader<S> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.size.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+        };
+        self.pos = target.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(self.pos)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{RangeReader, RangeSource};
+    use crate::zip::compression::CompressionMethod;
+    use crate::zip::read::ZipArchive;
+    use crate::zip::write::{FileOptions, ZipWriter};
+    use std::io::{self, Read, Seek, SeekFrom, Write};
+
+    /// An in-memory stand-in for a remote object
+    struct Remote(Vec<u8>);
+
+    impl RangeSource for Remote {
+        fn size(&mut self) -> io::Result<u64> {
+            Ok(self.0.len() as u64)
+        }
+
+        fn read_at(&mut self, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+            let start = offset as usize;
+            Ok(self.0[start..start + len].to_vec())
+        }
+    }
+
+    fn contents(i: usize) -> Vec<u8> {
+        (0..10_000).map(|j| (i * 31 + j * 7) as u8).collect()
+    }
+
+    #[test]
+    fn open_remote_archive() {
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+        for i in 0..200 {
+            writer
+                .start_file(format!("{i}.bin"), options.clone())
+                .unwrap();
+            writer.write_all(&contents(i)).unwrap();
+        }
+        let bytes = writer.finish().unwrap().into_inner();
+        let total = bytes.len() as u64;
+
+        let reader = RangeReader::with_cache(Remote(bytes), 4096, 16).unwrap();
+        let mut archive = ZipArchive::new(reader).unwrap();
+        assert_eq!(archive.len(), 200);
+        let mut file = Vec::new();
+        archive
+            .by_name("123.bin")
+            .unwrap()
+            .read_to_end(&mut file)
+            .unwrap();
+        assert_eq!(file, contents(123));
+
+        let reader = archive.into_inner();
+        assert!(reader.requests() < 20, "{} requests", reader.requests());
+        assert!(reader.bytes_fetched() < total / 10);
+    }
+
+    #[test]
+    fn cache_eviction() {
+        let data: Vec<u8> = (0..100u8).collect();
+        let mut reader = RangeReader::with_cache(Remote(data), 10, 2).unwrap();
+        let mut buf = [0; 5];
+
+        reader.seek(SeekFrom::Start(42)).unwrap();
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [42, 43, 44, 45, 46]);
+        reader.seek(SeekFrom::End(-3)).unwrap();
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, [97, 98, 99]);
+        assert_eq!(reader.requests(), 2);
+
+        // Both blocks are cached, the third one evicts the least recently used
+        reader.seek(SeekFrom::Start(40)).unwrap();
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(reader.requests(), 2);
+        reader.seek(SeekFrom::Start(0)).unwrap();
+        reader.read_exact(&mut buf).unwrap();
+        reader.seek(SeekFrom::Start(95)).unwrap();
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(reader.requests(), 4);
+        assert!(reader.seek(SeekFrom::Current(-200)).is_err());
+    }
+}
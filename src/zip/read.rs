@@ -3,12 +3,20 @@
 #[cfg(feature = "aes-crypto")]
 use crate::zip::aes::{AesReader, AesReaderValid};
 use crate::zip::compression::CompressionMethod;
-use crate::zip::cp437::FromCp437;
 use crate::zip::crc32::Crc32Reader;
+use crate::zip::encoding::Encoding;
+use crate::zip::manifest::{EncryptionKind, Manifest, ManifestEntry};
+use crate::zip::password::{Password, PasswordProvider};
+use crate::zip::progress::{self, Progress};
 use crate::zip::result::{InvalidPassword, ZipError, ZipResult};
+#[cfg(feature = "signing")]
+use crate::zip::signature;
 use crate::zip::spec;
-use crate::zip::types::{AesMode, AesVendorVersion, AtomicU64, DateTime, System, ZipFileData};
+use crate::zip::types::{
+    AesMode, AesVendorVersion, AtomicU64, DateTime, DosAttributes, System, ZipFileData,
+};
 use crate::zip::zipcrypto::{ZipCryptoReader, ZipCryptoReaderValid, ZipCryptoValidator};
+use crate::zip::zstd_dictionary;
 use byteorder::{LittleEndian, ReadBytesExt};
 use std::borrow::Cow;
 use std::collections::HashMap;
@@ -32,6 +40,19 @@ use zstd::stream::read::Decoder as ZstdDecoder;
 /// Provides high level API for reading from a stream.
 pub(crate) mod stream;
 
+mod index;
+mod lazy;
+mod nested;
+mod seekable;
+mod slice;
+mod tree;
+pub use index::{DirectoryTotals, EntryMetadata, MetadataIndex};
+pub use lazy::LazyZipArchive;
+pub use nested::{open_nested_path, NestedReader, ReadSeek, NESTED_PATH_SEPARATOR};
+pub use seekable::ZipFileSeek;
+pub use slice::ZipSliceReader;
+pub use tree::{NodeMetadata, TreeNode, ZipTree};
+
 // Put the struct declaration in a private module to convince rustdoc to display ZipArchive nicely
 pub(crate) mod zip_archive {
     /// Extract immutable data from `ZipArchive` to make it cheap to clone
@@ -67,6 +88,9 @@ pub(crate) mod zip_archive {
     pub struct ZipArchive<R> {
         pub(super) reader: R,
         pub(super) shared: super::Arc<Shared>,
+        /// The zstd dictionaries loaded so far, by id
+        #[cfg(feature = "zstd")]
+        pub(super) zstd_dictionaries: super::HashMap<u32, super::Arc<[u8]>>,
     }
 }
 
@@ -181,28 +205,59 @@ pub struct ZipFile<'a> {
     data: Cow<'a, ZipFileData>,
     crypto_reader: Option<CryptoReader<'a>>,
     reader: ZipFileReader<'a>,
+    #[cfg(feature = "zstd")]
+    zstd_dictionary: Option<Arc<[u8]>>,
 }
 
 fn find_content<'a>(
     data: &ZipFileData,
     reader: &'a mut (impl Read + Seek),
 ) -> ZipResult<io::Take<&'a mut dyn Read>> {
+    let data_start = find_data_start(data, reader)?;
+
+    reader.seek(io::SeekFrom::Start(data_start))?;
+    Ok((reader as &mut dyn Read).take(data.compressed_size))
+}
+
+/// Parse the local header of a file to find where its data starts.
+pub(crate) fn find_data_start(
+    data: &ZipFileData,
+    reader: &mut (impl Read + Seek),
+) -> ZipResult<u64> {
+    let header_error = |error: ZipError, offset: u64, field: &'static str| {
+        error
+            .with_offset(offset, field)
+            .with_entry_name(&data.file_name)
+    };
+
     // Parse local header
-    reader.seek(io::SeekFrom::Start(data.header_start))?;
-    let signature = reader.read_u32::<LittleEndian>()?;
+    let signature = reader
+        .seek(io::SeekFrom::Start(data.header_start))
+        .and_then(|_| reader.read_u32::<LittleEndian>())
+        .map_err(|e| header_error(e.into(), data.header_start, "local file header signature"))?;
     if signature != spec::LOCAL_FILE_HEADER_SIGNATURE {
-        return Err(ZipError::InvalidArchive("Invalid local file header"));
+        return Err(header_error(
+            ZipError::InvalidArchive("Invalid local file header"),
+            data.header_start,
+            "local file header signature",
+        ));
     }
 
-    reader.seek(io::SeekFrom::Current(22))?;
-    let file_name_length = reader.read_u16::<LittleEndian>()? as u64;
-    let extra_field_length = reader.read_u16::<LittleEndian>()? as u64;
+    let lengths_start = data.header_start + 26;
+    let (file_name_length, extra_field_length) = reader
+        .seek(io::SeekFrom::Start(lengths_start))
+        .and_then(|_| {
+            Ok((
+                reader.read_u16::<LittleEndian>()? as u64,
+                reader.read_u16::<LittleEndian>()? as u64,
+            ))
+        })
+        .map_err(|e| header_error(e.into(), lengths_start, "local file header lengths"))?;
     let magic_and_header = 4 + 22 + 2 + 2;
     let data_start = data.header_start + magic_and_header + file_name_length + extra_field_length;
     data.data_start.store(data_start);
 
-    reader.seek(io::SeekFrom::Start(data_start))?;
-    Ok((reader as &mut dyn Read).take(data.compressed_size))
+    Ok(data_start)
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -257,11 +312,12 @@ fn make_crypto_reader<'a>(
     Ok(Ok(reader))
 }
 
-fn make_reader(
+fn make_reader<'a>(
     compression_method: CompressionMethod,
     crc32: u32,
-    reader: CryptoReader,
-) -> ZipFileReader {
+    reader: CryptoReader<'a>,
+    #[cfg(feature = "zstd")] zstd_dictionary: Option<&[u8]>,
+) -> ZipFileReader<'a> {
     let ae2_encrypted = reader.is_ae2_encrypted();
 
     match compression_method {
@@ -284,7 +340,14 @@ fn make_reader(
         }
         #[cfg(feature = "zstd")]
         CompressionMethod::Zstd => {
-            let zstd_reader = ZstdDecoder::new(reader).unwrap();
+            // Dictionaries are checked by `load_zstd_dictionary`
+            let zstd_reader = match zstd_dictionary {
+                Some(dictionary) => {
+                    ZstdDecoder::with_dictionary(io::BufReader::new(reader), dictionary)
+                }
+                None => ZstdDecoder::new(reader),
+            }
+            .unwrap();
             ZipFileReader::Zstd(Crc32Reader::new(zstd_reader, crc32, ae2_encrypted))
         }
         _ => panic!("Compression method not supported"),
@@ -398,7 +461,15 @@ impl<R: Read + io::Seek> ZipArchive<R> {
     /// Read a ZIP archive, collecting the files it contains
     ///
     /// This uses the central directory record of the ZIP file, and ignores local file headers
-    pub fn new(mut reader: R) -> ZipResult<ZipArchive<R>> {
+    pub fn new(reader: R) -> ZipResult<ZipArchive<R>> {
+        Self::with_name_encoding(reader, Encoding::Cp437)
+    }
+
+    /// Read a ZIP archive like [`ZipArchive::new`], decoding the file names and comments that
+    /// are not marked as UTF-8 with `encoding` instead of CP437
+    ///
+    /// With [`Encoding::Auto`], the encoding is detected from the names of all the files.
+    pub fn with_name_encoding(mut reader: R, encoding: Encoding) -> ZipResult<ZipArchive<R>> {
         let (footer, cde_start_pos) = spec::CentralDirectoryEnd::find_and_parse(&mut reader)?;
 
         if !footer.record_too_small() && footer.disk_number != footer.disk_with_central_directory {
@@ -416,19 +487,37 @@ impl<R: Read + io::Seek> ZipArchive<R> {
             number_of_files
         };
 
-        let mut files = Vec::with_capacity(file_capacity);
-        let mut names_map = HashMap::with_capacity(file_capacity);
+        let read_files = |reader: &mut R, encoding| {
+            if reader.seek(io::SeekFrom::Start(directory_start)).is_err() {
+                return Err(ZipError::InvalidArchive(
+                    "Could not seek to start of central directory",
+                ));
+            }
+            let mut files = Vec::with_capacity(file_capacity);
+            for index in 0..number_of_files {
+                let file = central_header_to_zip_file(reader, archive_offset, encoding)
+                    .map_err(|e| e.with_entry_index(index))?;
+                files.push(file);
+            }
+            Ok(files)
+        };
 
-        if reader.seek(io::SeekFrom::Start(directory_start)).is_err() {
-            return Err(ZipError::InvalidArchive(
-                "Could not seek to start of central directory",
-            ));
+        // With `Auto`, the names are decoded as CP437 until the encoding is detected
+        let mut files = match encoding {
+            Encoding::Auto => read_files(&mut reader, Encoding::Cp437)?,
+            encoding => read_files(&mut reader, encoding)?,
+        };
+        if encoding == Encoding::Auto {
+            // Read the central directory again to decode the comments as well
+            let detected = Encoding::detect(files.iter().map(|file| &file.file_name_raw[..]));
+            if detected != Encoding::Cp437 {
+                files = read_files(&mut reader, detected)?;
+            }
         }
 
-        for _ in 0..number_of_files {
-            let file = central_header_to_zip_file(&mut reader, archive_offset)?;
-            names_map.insert(file.file_name.clone(), files.len());
-            files.push(file);
+        let mut names_map = HashMap::with_capacity(files.len());
+        for (index, file) in files.iter().enumerate() {
+            names_map.insert(file.file_name.clone(), index);
         }
 
         let shared = Arc::new(zip_archive::Shared {
@@ -438,18 +527,58 @@ impl<R: Read + io::Seek> ZipArchive<R> {
             comment: footer.zip_file_comment,
         });
 
-        Ok(ZipArchive { reader, shared })
+        Ok(ZipArchive {
+            reader,
+            shared,
+            #[cfg(feature = "zstd")]
+            zstd_dictionaries: HashMap::new(),
+        })
     }
     /// Extract a Zip archive into a directory, overwriting files if they
     /// already exist. Paths are sanitized with [`ZipFile::enclosed_name`].
     ///
     /// Extraction is not atomic; If an error is encountered, some of the files
     /// may be left on disk.
+    ///
+    /// The zstd dictionaries stored by [`crate::zip::write::ZipWriter::set_zstd_dictionary`]
+    /// are not extracted, see [`ZipFile::is_zstd_dictionary`].
     pub fn extract<P: AsRef<Path>>(&mut self, directory: P) -> ZipResult<()> {
+        self.extract_with_passwords(directory, &mut |_: &str, _| None)
+    }
+
+    /// Extract a Zip archive into a directory like [`ZipArchive::extract`], decrypting
+    /// encrypted files with the passwords given by `passwords`.
+    pub fn extract_with_passwords<P: AsRef<Path>>(
+        &mut self,
+        directory: P,
+        passwords: &mut dyn PasswordProvider,
+    ) -> ZipResult<()> {
+        self.extract_with_progress(directory, passwords, &mut ())
+    }
+
+    /// Extract a Zip archive into a directory like [`ZipArchive::extract_with_passwords`],
+    /// reporting every file and the extracted data to `progress`.
+    ///
+    /// When the extraction is cancelled, the file being extracted is left incomplete on disk.
+    pub fn extract_with_progress<P: AsRef<Path>>(
+        &mut self,
+        directory: P,
+        passwords: &mut dyn PasswordProvider,
+        progress: &mut dyn Progress,
+    ) -> ZipResult<()> {
         use std::fs;
 
+        // Directory modes are set once all the files are written, in case they are read-only
+        #[cfg(unix)]
+        let mut directory_modes = Vec::new();
         for i in 0..self.len() {
-            let mut file = self.by_index(i)?;
+            if zstd_dictionary::is_dictionary(&self.shared.files[i]) {
+                continue;
+            }
+            let mut file = self
+                .by_index_with_passwords(i, passwords)?
+                .map_err(|_| ZipError::UnsupportedArchive("Invalid password"))?;
+            progress::start_entry(progress, file.name(), Some(file.size()))?;
             let filepath = file
                 .enclosed_name()
                 .ok_or(ZipError::InvalidArchive("Invalid file path"))?;
@@ -465,17 +594,29 @@ impl<R: Read + io::Seek> ZipArchive<R> {
                     }
                 }
                 let mut outfile = fs::File::create(&outpath)?;
-                io::copy(&mut file, &mut outfile)?;
+                progress::copy(&mut file, &mut outfile, progress)?;
             }
             // Get and Set permissions
             #[cfg(unix)]
             {
                 use std::os::unix::fs::PermissionsExt;
-                if let Some(mode) = file.unix_mode() {
-                    fs::set_permissions(&outpath, fs::Permissions::from_mode(mode))?;
+                if let Some(mode) = file.extracted_unix_mode() {
+                    if file.name().ends_with('/') {
+                        directory_modes.push((outpath, mode));
+                    } else {
+                        fs::set_permissions(&outpath, fs::Permissions::from_mode(mode))?;
+                    }
                 }
             }
         }
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            // Subdirectories first, while their parents are still writable
+            for (outpath, mode) in directory_modes.into_iter().rev() {
+                fs::set_permissions(&outpath, fs::Permissions::from_mode(mode))?;
+            }
+        }
         Ok(())
     }
 
@@ -502,11 +643,74 @@ impl<R: Read + io::Seek> ZipArchive<R> {
         &self.shared.comment
     }
 
+    /// Describe every entry of the archive, without reading any file data.
+    ///
+    /// The local file headers are parsed to find the offset of the file data, but the file
+    /// data itself is not read. With the `serialize` feature, the result can be exported
+    /// with serde, e.g. as JSON, for auditing.
+    pub fn manifest(&mut self) -> ZipResult<Manifest> {
+        let reader = &mut self.reader;
+        let entries = self
+            .shared
+            .files
+            .iter()
+            .map(|data| Ok(ManifestEntry::new(data, find_data_start(data, reader)?)))
+            .collect::<ZipResult<Vec<_>>>()?;
+
+        Ok(Manifest {
+            offset: self.shared.offset,
+            comment: self.shared.comment.clone(),
+            entries,
+        })
+    }
+
+    /// Index the modification times and sizes of the entries, to find the files modified
+    /// in a time window, the largest files and the totals of each directory
+    ///
+    /// The index is built from the central directory, so no file data is read. The zstd
+    /// dictionaries are left out, see [`ZipFile::is_zstd_dictionary`].
+    pub fn metadata_index(&self) -> MetadataIndex {
+        let entries = self
+            .shared
+            .files
+            .iter()
+            .enumerate()
+            .filter(|(_, data)| !zstd_dictionary::is_dictionary(data))
+            .map(|(index, data)| EntryMetadata::new(index, data))
+            .collect();
+        MetadataIndex::new(entries)
+    }
+
+    /// Check the Ed25519 signature of an archive written with [`crate::zip::write::ZipWriter::finish_signed`]
+    ///
+    /// Fails with [`ZipError::InvalidArchive`] if the archive is not signed, is signed with
+    /// another key, or has been modified since it was signed. The signature is stored at the
+    /// end of the archive comment, so [`ZipArchive::comment`] returns it as well.
+    #[cfg(feature = "signing")]
+    pub fn verify_signature(&mut self, public_key: &[u8; 32]) -> ZipResult<()> {
+        signature::verify(&mut self.reader, &self.shared.comment, public_key)
+    }
+
+    /// Id of the key that signed the archive, if it is signed
+    ///
+    /// The id is the first 8 bytes of the SHA-512 digest of the Ed25519 public key. It can
+    /// be used to pick the key to check the signature with.
+    #[cfg(feature = "signing")]
+    pub fn signature_key_id(&self) -> Option<[u8; 8]> {
+        signature::signer(&self.shared.comment)
+    }
+
     /// Returns an iterator over all the file and directory names in this archive.
     pub fn file_names(&self) -> impl Iterator<Item = &str> {
         self.shared.names_map.keys().map(|s| s.as_str())
     }
 
+    /// Browse the archive as a directory tree, including the directories that only appear in
+    /// the names of other files
+    pub fn tree(&mut self) -> ZipTree<'_, R> {
+        ZipTree::new(self)
+    }
+
     /// Search for a file entry by name, decrypt with given password
     ///
     /// # Warning
@@ -575,26 +779,93 @@ impl<R: Read + io::Seek> ZipArchive<R> {
             .unwrap())
     }
 
+    /// Search for a file entry by name, asking `passwords` for the password if it is encrypted
+    ///
+    /// The password is wiped from memory as soon as the decryption keys are derived from it.
+    /// The same warnings as for [`ZipArchive::by_name_decrypt`] apply.
+    pub fn by_name_with_passwords<'a>(
+        &'a mut self,
+        name: &str,
+        passwords: &mut dyn PasswordProvider,
+    ) -> ZipResult<Result<ZipFile<'a>, InvalidPassword>> {
+        let index = *self
+            .shared
+            .names_map
+            .get(name)
+            .ok_or(ZipError::FileNotFound)?;
+        self.by_index_with_passwords(index, passwords)
+    }
+
+    /// Get a contained file by index, asking `passwords` for the password if it is encrypted
+    ///
+    /// The password is wiped from memory as soon as the decryption keys are derived from it.
+    /// The same warnings as for [`ZipArchive::by_index_decrypt`] apply.
+    pub fn by_index_with_passwords<'a>(
+        &'a mut self,
+        file_number: usize,
+        passwords: &mut dyn PasswordProvider,
+    ) -> ZipResult<Result<ZipFile<'a>, InvalidPassword>> {
+        let data = self
+            .shared
+            .files
+            .get(file_number)
+            .ok_or(ZipError::FileNotFound)?;
+        let password = if data.encrypted {
+            let password = passwords
+                .password(&data.file_name, EncryptionKind::of(data))
+                .ok_or(ZipError::UnsupportedArchive(ZipError::PASSWORD_REQUIRED))?;
+            Some(password)
+        } else {
+            None
+        };
+        self.by_index_with_optional_password(file_number, password.as_ref().map(|p| p.as_slice()))
+    }
+
+    /// Search for a file entry by name, returning a reader that supports seeking
+    ///
+    /// Only unencrypted files are supported, and they must either be `Stored` or compressed
+    /// with `Zstd` using the [seekable format], in which case the seek table at the end of the
+    /// data is used to decompress only the frames that are needed.
+    ///
+    /// The CRC32 of the contents is not checked, since the file is usually not read in full.
+    ///
+    /// [seekable format]: https://github.com/facebook/zstd/blob/dev/contrib/seekable_format/zstd_seekable_compression_format.md
+    pub fn by_name_seekable(&mut self, name: &str) -> ZipResult<ZipFileSeek<'_, R>> {
+        let index = match self.shared.names_map.get(name) {
+            Some(index) => *index,
+            None => {
+                return Err(ZipError::FileNotFound);
+            }
+        };
+        self.by_index_seekable(index)
+    }
+
+    /// Get a contained file by index, returning a reader that supports seeking
+    ///
+    /// See [`ZipArchive::by_name_seekable`] for the files that can be read this way.
+    pub fn by_index_seekable(&mut self, file_number: usize) -> ZipResult<ZipFileSeek<'_, R>> {
+        let data = self
+            .shared
+            .files
+            .get(file_number)
+            .ok_or(ZipError::FileNotFound)?;
+        ZipFileSeek::new(data, &mut self.reader)
+    }
+
     /// Get a contained file by index without decompressing it
     pub fn by_index_raw(&mut self, file_number: usize) -> ZipResult<ZipFile<'_>> {
-        let reader = &mut self.reader;
-        self.shared
+        let data = self
+            .shared
             .files
             .get(file_number)
-            .ok_or(ZipError::FileNotFound)
-            .and_then(move |data| {
-                Ok(ZipFile {
-                    crypto_reader: None,
-                    reader: ZipFileReader::Raw(find_content(data, reader)?),
-                    data: Cow::Borrowed(data),
-                })
-            })
+            .ok_or(ZipError::FileNotFound)?;
+        open_raw_file(Cow::Borrowed(data), &mut self.reader)
     }
 
     fn by_index_with_optional_password<'a>(
         &'a mut self,
         file_number: usize,
-        mut password: Option<&[u8]>,
+        password: Option<&[u8]>,
     ) -> ZipResult<Result<ZipFile<'a>, InvalidPassword>> {
         let data = self
             .shared
@@ -602,31 +873,88 @@ impl<R: Read + io::Seek> ZipArchive<R> {
             .get(file_number)
             .ok_or(ZipError::FileNotFound)?;
 
-        match (password, data.encrypted) {
-            (None, true) => return Err(ZipError::UnsupportedArchive(ZipError::PASSWORD_REQUIRED)),
-            (Some(_), false) => password = None, //Password supplied, but none needed! Discard.
-            _ => {}
-        }
-        let limit_reader = find_content(data, &mut self.reader)?;
-
-        match make_crypto_reader(
-            data.compression_method,
-            data.crc32,
-            data.last_modified_time,
-            data.using_data_descriptor,
-            limit_reader,
+        #[cfg(feature = "zstd")]
+        let zstd_dictionary = match zstd_dictionary::used_by(data) {
+            Some(id) => {
+                let files = &self.shared.files;
+                let entry = self
+                    .shared
+                    .names_map
+                    .get(&zstd_dictionary::name(id))
+                    .map(|&index| Cow::Borrowed(&files[index]));
+                let dictionary =
+                    load_zstd_dictionary(id, entry, &mut self.reader, &mut self.zstd_dictionaries)
+                        .map_err(|e| e.with_entry_name(&data.file_name))?;
+                Some(dictionary)
+            }
+            None => None,
+        };
+
+        open_file(
+            Cow::Borrowed(data),
+            &mut self.reader,
             password,
-            data.aes_mode,
-            #[cfg(feature = "aes-crypto")]
-            data.compressed_size,
-        ) {
-            Ok(Ok(crypto_reader)) => Ok(Ok(ZipFile {
-                crypto_reader: Some(crypto_reader),
-                reader: ZipFileReader::NoReader,
-                data: Cow::Borrowed(data),
-            })),
-            Err(e) => Err(e),
-            Ok(Err(e)) => Ok(Err(e)),
+            #[cfg(feature = "zstd")]
+            zstd_dictionary,
+        )
+    }
+
+    /// Open a ZIP archive stored as a file of this archive
+    ///
+    /// `Stored` inner archives are read in place, other ones are decompressed first, see
+    /// [`NestedReader`].
+    pub fn open_nested(&mut self, name: &str) -> ZipResult<ZipArchive<NestedReader<&mut R>>> {
+        let index = *self
+            .shared
+            .names_map
+            .get(name)
+            .ok_or(ZipError::FileNotFound)?;
+        let source = nested::NestedSource::new(self, index)?;
+        ZipArchive::new(source.into_reader(&mut self.reader)?).map_err(|e| e.with_entry_name(name))
+    }
+
+    /// Open a ZIP archive stored as a file of this archive, taking ownership of this archive
+    ///
+    /// See [`ZipArchive::open_nested`].
+    pub fn into_nested(mut self, name: &str) -> ZipResult<ZipArchive<NestedReader<R>>> {
+        let index = *self
+            .shared
+            .names_map
+            .get(name)
+            .ok_or(ZipError::FileNotFound)?;
+        let source = nested::NestedSource::new(&mut self, index)?;
+        ZipArchive::new(source.into_reader(self.reader)?).map_err(|e| e.with_entry_name(name))
+    }
+
+    /// Open the archives nested in this one along a path like `inner.zip!/other.zip!/file.txt`
+    ///
+    /// Every part of the path but the last one is the name of an archive in the previous
+    /// one. Returns the innermost archive and the name of the file in it, which is empty when
+    /// the path ends with [`NESTED_PATH_SEPARATOR`].
+    pub fn into_nested_path(self, path: &str) -> ZipResult<(ZipArchive<Box<dyn ReadSeek>>, String)>
+    where
+        R: 'static,
+    {
+        let mut archive = self.boxed();
+        let mut parts = path.split(NESTED_PATH_SEPARATOR).peekable();
+        while let Some(part) = parts.next() {
+            if parts.peek().is_none() {
+                return Ok((archive, part.to_owned()));
+            }
+            archive = archive.into_nested(part)?.boxed();
+        }
+        unreachable!("split always returns at least one part")
+    }
+
+    fn boxed(self) -> ZipArchive<Box<dyn ReadSeek>>
+    where
+        R: 'static,
+    {
+        ZipArchive {
+            reader: Box::new(self.reader),
+            shared: self.shared,
+            #[cfg(feature = "zstd")]
+            zstd_dictionaries: self.zstd_dictionaries,
         }
     }
 
@@ -638,6 +966,211 @@ impl<R: Read + io::Seek> ZipArchive<R> {
     }
 }
 
+#[cfg(feature = "mmap")]
+impl ZipArchive<io::Cursor<memmap2::Mmap>> {
+    /// Open a ZIP archive by mapping the file into memory, read-only
+    ///
+    /// The contents of `Stored` files can then be borrowed directly from the mapping with
+    /// [`ZipArchive::by_name_slice`], and compressed files can be decompressed straight from
+    /// it with [`ZipArchive::by_name_slice_reader`], without seeking. Files opened with
+    /// [`ZipArchive::by_name`] still go through the ordinary seek and read path.
+    ///
+    /// # Warning
+    ///
+    /// The file must not be modified or truncated while it is mapped, as this would change
+    /// the data of the archive behind its back, or even crash the process.
+    pub fn open_mmap<P: AsRef<Path>>(path: P) -> ZipResult<Self> {
+        let file = std::fs::File::open(path)?;
+        // SAFETY: the mapping is read-only, and the caller is told not to modify the file.
+        let mmap = unsafe { memmap2::Mmap::map(&file)? };
+        ZipArchive::new(io::Cursor::new(mmap))
+    }
+}
+
+impl<T: AsRef<[u8]>> ZipArchive<io::Cursor<T>> {
+    /// Search for a file entry by name, borrowing its contents from the in-memory archive
+    ///
+    /// Only unencrypted `Stored` files can be borrowed, since all other files have to be
+    /// decompressed or decrypted first. The CRC32 of the contents is not checked.
+    pub fn by_name_slice(&self, name: &str) -> ZipResult<&[u8]> {
+        let index = match self.shared.names_map.get(name) {
+            Some(index) => *index,
+            None => {
+                return Err(ZipError::FileNotFound);
+            }
+        };
+        self.by_index_slice(index)
+    }
+
+    /// Get a contained file by index, borrowing its contents from the in-memory archive
+    ///
+    /// See [`ZipArchive::by_name_slice`] for the files that can be borrowed.
+    pub fn by_index_slice(&self, file_number: usize) -> ZipResult<&[u8]> {
+        let data = self
+            .shared
+            .files
+            .get(file_number)
+            .ok_or(ZipError::FileNotFound)?;
+        if data.encrypted || data.compression_method != CompressionMethod::Stored {
+            return unsupported_zip_error("Only unencrypted stored files can be borrowed");
+        }
+        self.file_bytes(data)
+    }
+
+    /// Search for a file entry by name, decompressing it straight from the in-memory archive
+    ///
+    /// Only unencrypted files are supported. Unlike [`ZipArchive::by_name`], the archive is
+    /// borrowed immutably, so several files can be read at the same time.
+    pub fn by_name_slice_reader(&self, name: &str) -> ZipResult<ZipSliceReader<'_>> {
+        let index = *self
+            .shared
+            .names_map
+            .get(name)
+            .ok_or(ZipError::FileNotFound)?;
+        self.by_index_slice_reader(index)
+    }
+
+    /// Get a contained file by index, decompressing it straight from the in-memory archive
+    ///
+    /// See [`ZipArchive::by_name_slice_reader`].
+    pub fn by_index_slice_reader(&self, file_number: usize) -> ZipResult<ZipSliceReader<'_>> {
+        let data = self
+            .shared
+            .files
+            .get(file_number)
+            .ok_or(ZipError::FileNotFound)?;
+        if data.encrypted {
+            return unsupported_zip_error("Encrypted files cannot be read from slices");
+        }
+
+        #[cfg(feature = "zstd")]
+        let zstd_dictionary = match zstd_dictionary::used_by(data) {
+            Some(id) => {
+                let dictionary = self
+                    .shared
+                    .names_map
+                    .get(&zstd_dictionary::name(id))
+                    .map(|&index| &self.shared.files[index])
+                    .filter(|entry| {
+                        !entry.encrypted && entry.compression_method == CompressionMethod::Stored
+                    })
+                    .ok_or(ZipError::InvalidArchive("Missing zstd dictionary"))
+                    .and_then(|entry| self.file_bytes(entry))?;
+                if zstd_dictionary::dictionary_id(dictionary) != Some(id) {
+                    return Err(ZipError::InvalidArchive("Invalid zstd dictionary"));
+                }
+                Some(dictionary)
+            }
+            None => None,
+        };
+
+        let reader = ZipSliceReader::new(
+            data,
+            self.file_bytes(data)?,
+            #[cfg(feature = "zstd")]
+            zstd_dictionary,
+        )
+        .map_err(|e| ZipError::from(e).with_entry_name(&data.file_name))?;
+        Ok(reader)
+    }
+
+    /// The compressed data of a file, borrowed from the in-memory archive
+    fn file_bytes(&self, data: &ZipFileData) -> ZipResult<&[u8]> {
+        let bytes = self.reader.get_ref().as_ref();
+        let data_start = find_data_start(data, &mut io::Cursor::new(bytes))?;
+        let data_end = data_start
+            .checked_add(data.compressed_size)
+            .filter(|&end| end <= bytes.len() as u64)
+            .ok_or(ZipError::InvalidArchive("File data exceeds the archive"))?;
+        Ok(&bytes[data_start as usize..data_end as usize])
+    }
+}
+
+/// Open the file described by `data`, without decompressing it.
+pub(crate) fn open_raw_file<'a, R: Read + Seek>(
+    data: Cow<'a, ZipFileData>,
+    reader: &'a mut R,
+) -> ZipResult<ZipFile<'a>> {
+    Ok(ZipFile {
+        crypto_reader: None,
+        reader: ZipFileReader::Raw(find_content(&data, reader)?),
+        data,
+        #[cfg(feature = "zstd")]
+        zstd_dictionary: None,
+    })
+}
+
+/// Open the file described by `data`, decrypting it with `password` if it is encrypted.
+///
+/// Files compressed with a zstd dictionary need the dictionary loaded by
+/// [`load_zstd_dictionary`].
+pub(crate) fn open_file<'a, R: Read + Seek>(
+    data: Cow<'a, ZipFileData>,
+    reader: &'a mut R,
+    mut password: Option<&[u8]>,
+    #[cfg(feature = "zstd")] zstd_dictionary: Option<Arc<[u8]>>,
+) -> ZipResult<Result<ZipFile<'a>, InvalidPassword>> {
+    match (password, data.encrypted) {
+        (None, true) => return Err(ZipError::UnsupportedArchive(ZipError::PASSWORD_REQUIRED)),
+        (Some(_), false) => password = None, //Password supplied, but none needed! Discard.
+        _ => {}
+    }
+    let limit_reader = find_content(&data, reader)?;
+
+    match make_crypto_reader(
+        data.compression_method,
+        data.crc32,
+        data.last_modified_time,
+        data.using_data_descriptor,
+        limit_reader,
+        password,
+        data.aes_mode,
+        #[cfg(feature = "aes-crypto")]
+        data.compressed_size,
+    ) {
+        Ok(Ok(crypto_reader)) => Ok(Ok(ZipFile {
+            crypto_reader: Some(crypto_reader),
+            reader: ZipFileReader::NoReader,
+            data,
+            #[cfg(feature = "zstd")]
+            zstd_dictionary,
+        })),
+        Err(e) => Err(e),
+        Ok(Err(e)) => Ok(Err(e)),
+    }
+}
+
+/// Load the zstd dictionary with the given id from its `entry`, unless it is in `cache`.
+#[cfg(feature = "zstd")]
+pub(crate) fn load_zstd_dictionary<R: Read + Seek>(
+    id: u32,
+    entry: Option<Cow<ZipFileData>>,
+    reader: &mut R,
+    cache: &mut HashMap<u32, Arc<[u8]>>,
+) -> ZipResult<Arc<[u8]>> {
+    if let Some(dictionary) = cache.get(&id) {
+        return Ok(dictionary.clone());
+    }
+
+    let entry = entry.ok_or(ZipError::InvalidArchive("Missing zstd dictionary"))?;
+    if entry.encrypted || entry.compression_method != CompressionMethod::Stored {
+        return Err(ZipError::InvalidArchive("Invalid zstd dictionary"));
+    }
+    let mut dictionary = Vec::new();
+    open_file(entry, reader, None, None)?
+        .unwrap()
+        .read_to_end(&mut dictionary)?;
+    if zstd_dictionary::dictionary_id(&dictionary) != Some(id)
+        || zstd::stream::raw::Decoder::with_dictionary(&dictionary).is_err()
+    {
+        return Err(ZipError::InvalidArchive("Invalid zstd dictionary"));
+    }
+
+    let dictionary: Arc<[u8]> = dictionary.into();
+    cache.insert(id, dictionary.clone());
+    Ok(dictionary)
+}
+
 fn unsupported_zip_error<T>(detail: &'static str) -> ZipResult<T> {
     Err(ZipError::UnsupportedArchive(detail))
 }
@@ -646,23 +1179,35 @@ fn unsupported_zip_error<T>(detail: &'static str) -> ZipResult<T> {
 pub(crate) fn central_header_to_zip_file<R: Read + io::Seek>(
     reader: &mut R,
     archive_offset: u64,
+    name_encoding: Encoding,
 ) -> ZipResult<ZipFileData> {
     let central_header_start = reader.stream_position()?;
 
     // Parse central header
-    let signature = reader.read_u32::<LittleEndian>()?;
+    let signature = reader.read_u32::<LittleEndian>().map_err(|e| {
+        ZipError::from(e).with_offset(central_header_start, "central directory header signature")
+    })?;
     if signature != spec::CENTRAL_DIRECTORY_HEADER_SIGNATURE {
-        Err(ZipError::InvalidArchive("Invalid Central Directory header"))
+        Err(ZipError::InvalidArchive("Invalid Central Directory header")
+            .with_offset(central_header_start, "central directory header signature"))
     } else {
-        central_header_to_zip_file_inner(reader, archive_offset, central_header_start)
+        central_header_to_zip_file_inner(
+            reader,
+            archive_offset,
+            central_header_start,
+            name_encoding,
+        )
+        .map_err(|e| e.with_offset(central_header_start, "central directory header"))
     }
 }
 
-/// Parse a central directory entry to collect the information for the file.
-fn central_header_to_zip_file_inner<R: Read>(
+/// Parse a central directory entry after its signature to collect the information for the
+/// file.
+pub(crate) fn central_header_to_zip_file_inner<R: Read>(
     reader: &mut R,
     archive_offset: u64,
     central_header_start: u64,
+    name_encoding: Encoding,
 ) -> ZipResult<ZipFileData> {
     let version_made_by = reader.read_u16::<LittleEndian>()?;
     let _version_to_extract = reader.read_u16::<LittleEndian>()?;
@@ -690,14 +1235,13 @@ fn central_header_to_zip_file_inner<R: Read>(
     let mut file_comment_raw = vec![0; file_comment_length];
     reader.read_exact(&mut file_comment_raw)?;
 
-    let file_name = match is_utf8 {
-        true => String::from_utf8_lossy(&file_name_raw).into_owned(),
-        false => file_name_raw.clone().from_cp437(),
-    };
-    let file_comment = match is_utf8 {
-        true => String::from_utf8_lossy(&file_comment_raw).into_owned(),
-        false => file_comment_raw.from_cp437(),
+    let text_encoding = if is_utf8 {
+        Encoding::Utf8
+    } else {
+        name_encoding.for_name(&file_name_raw)
     };
+    let file_name = text_encoding.decode(&file_name_raw).into_owned();
+    let file_comment = text_encoding.decode(&file_comment_raw).into_owned();
 
     // Construct the result
     let mut result = ZipFileData {
@@ -726,23 +1270,37 @@ fn central_header_to_zip_file_inner<R: Read>(
         aes_mode: None,
     };
 
+    let entry_error = |error: ZipError, field: &'static str, name: &str| {
+        error
+            .with_offset(central_header_start, field)
+            .with_entry_name(name)
+    };
+
     match parse_extra_field(&mut result) {
         Ok(..) | Err(ZipError::Io(..)) => {}
-        Err(e) => return Err(e),
+        Err(e) => return Err(entry_error(e, "extra field", &result.file_name)),
     }
 
     let aes_enabled = result.compression_method == CompressionMethod::AES;
     if aes_enabled && result.aes_mode.is_none() {
-        return Err(ZipError::InvalidArchive(
-            "AES encryption without AES extra data field",
+        return Err(entry_error(
+            ZipError::InvalidArchive("AES encryption without AES extra data field"),
+            "extra field",
+            &result.file_name,
         ));
     }
 
     // Account for shifted zip offsets.
-    result.header_start = result
-        .header_start
-        .checked_add(archive_offset)
-        .ok_or(ZipError::InvalidArchive("Archive header is too large"))?;
+    result.header_start = match result.header_start.checked_add(archive_offset) {
+        Some(header_start) => header_start,
+        None => {
+            return Err(entry_error(
+                ZipError::InvalidArchive("Archive header is too large"),
+                "local header offset",
+                &result.file_name,
+            ))
+        }
+    };
 
     Ok(result)
 }
@@ -822,7 +1380,13 @@ impl<'a> ZipFile<'a> {
         if let ZipFileReader::NoReader = self.reader {
             let data = &self.data;
             let crypto_reader = self.crypto_reader.take().expect("Invalid reader state");
-            self.reader = make_reader(data.compression_method, data.crc32, crypto_reader)
+            self.reader = make_reader(
+                data.compression_method,
+                data.crc32,
+                crypto_reader,
+                #[cfg(feature = "zstd")]
+                self.zstd_dictionary.as_deref(),
+            )
         }
         &mut self.reader
     }
@@ -950,6 +1514,41 @@ impl<'a> ZipFile<'a> {
         self.data.unix_mode()
     }
 
+    /// Get the MS-DOS and Windows attributes of the file, if known
+    pub fn dos_attributes(&self) -> Option<DosAttributes> {
+        self.data.dos_attributes()
+    }
+
+    /// Get the unix mode to extract the file with: [`ZipFile::unix_mode`] without the write
+    /// permissions when the file has the MS-DOS read-only attribute
+    pub fn extracted_unix_mode(&self) -> Option<u32> {
+        self.data.extracted_unix_mode()
+    }
+
+    /// Returns whether the file stores a zstd dictionary written by
+    /// [`crate::zip::write::ZipWriter::set_zstd_dictionary`] rather than user content
+    ///
+    /// These files are named `.zstd-dictionaries/<id>`, with the id of the dictionary as 8
+    /// hexadecimal digits, and are stored without compression.
+    pub fn is_zstd_dictionary(&self) -> bool {
+        zstd_dictionary::is_dictionary(&self.data)
+    }
+
+    /// Returns whether the file is encrypted
+    pub fn encrypted(&self) -> bool {
+        self.data.encrypted
+    }
+
+    /// Returns whether the file is encrypted with AES rather than ZipCrypto
+    pub(crate) fn aes_encrypted(&self) -> bool {
+        self.data.aes_mode.is_some()
+    }
+
+    /// Returns whether reading the file gives its raw data, as stored in the archive
+    pub(crate) fn is_raw(&self) -> bool {
+        matches!(self.reader, ZipFileReader::Raw(_))
+    }
+
     /// Get the CRC32 hash of the original file
     pub fn crc32(&self) -> u32 {
         self.data.crc32
@@ -1029,9 +1628,51 @@ impl<'a> Drop for ZipFile<'a> {
 /// * `comment`: set to an empty string
 /// * `data_start`: set to 0
 /// * `external_attributes`: `unix_mode()`: will return None
-pub fn read_zipfile_from_stream<'a, R: io::Read>(
+pub fn read_zipfile_from_stream<R: io::Read>(reader: &mut R) -> ZipResult<Option<ZipFile<'_>>> {
+    read_zipfile_from_stream_with_passwords(reader, &mut |_: &str, _: EncryptionKind| None)
+}
+
+/// Read ZipFile structures from a non-seekable reader like [`read_zipfile_from_stream`],
+/// decrypting encrypted files with `password`.
+///
+/// # Warning
+///
+/// Checking the password consumes the start of the file data, so the stream cannot be read
+/// any further after an invalid password. See [`ZipArchive::by_name_decrypt`] for the
+/// weaknesses of ZipCrypto.
+pub fn read_zipfile_from_stream_decrypt<'a, R: io::Read>(
     reader: &'a mut R,
-) -> ZipResult<Option<ZipFile<'_>>> {
+    password: &[u8],
+) -> ZipResult<Option<ZipFile<'a>>> {
+    read_zipfile_from_stream_with_passwords(reader, &mut |_: &str, _: EncryptionKind| {
+        Some(Password::new(password.to_vec()))
+    })
+}
+
+/// Read ZipFile structures from a non-seekable reader like [`read_zipfile_from_stream`],
+/// decrypting encrypted files with the passwords given by `passwords`.
+///
+/// Fails with [`ZipError::UnsupportedArchive`] when the password of an encrypted file is
+/// missing or invalid, see [`read_zipfile_from_stream_decrypt`].
+pub fn read_zipfile_from_stream_with_passwords<'a, R: io::Read>(
+    reader: &'a mut R,
+    passwords: &mut dyn PasswordProvider,
+) -> ZipResult<Option<ZipFile<'a>>> {
+    read_zipfile_from_stream_with_name_encoding(reader, passwords, Encoding::Cp437)
+}
+
+/// Read ZipFile structures from a non-seekable reader like
+/// [`read_zipfile_from_stream_with_passwords`], decoding the file names that are not marked as
+/// UTF-8 with `encoding` instead of CP437
+///
+/// The other files of the archive are not known yet, so [`Encoding::Auto`] detects the
+/// encoding from the name of each file on its own, which is less reliable than with
+/// [`ZipArchive::with_name_encoding`].
+pub fn read_zipfile_from_stream_with_name_encoding<'a, R: io::Read>(
+    reader: &'a mut R,
+    passwords: &mut dyn PasswordProvider,
+    encoding: Encoding,
+) -> ZipResult<Option<ZipFile<'a>>> {
     let signature = reader.read_u32::<LittleEndian>()?;
 
     match signature {
@@ -1061,9 +1702,11 @@ pub fn read_zipfile_from_stream<'a, R: io::Read>(
     reader.read_exact(&mut extra_field)?;
 
     let file_name = match is_utf8 {
-        true => String::from_utf8_lossy(&file_name_raw).into_owned(),
-        false => file_name_raw.clone().from_cp437(),
-    };
+        true => Encoding::Utf8,
+        false => encoding.for_name(&file_name_raw),
+    }
+    .decode(&file_name_raw)
+    .into_owned();
 
     let mut result = ZipFileData {
         system: System::from_u8((version_made_by >> 8) as u8),
@@ -1098,12 +1741,17 @@ pub fn read_zipfile_from_stream<'a, R: io::Read>(
         Err(e) => return Err(e),
     }
 
-    if encrypted {
-        return unsupported_zip_error("Encrypted files are not supported");
-    }
     if using_data_descriptor {
         return unsupported_zip_error("The file length is not available in the local header");
     }
+    let password = if encrypted {
+        let password = passwords
+            .password(&result.file_name, EncryptionKind::of(&result))
+            .ok_or(ZipError::UnsupportedArchive(ZipError::PASSWORD_REQUIRED))?;
+        Some(password)
+    } else {
+        None
+    };
 
     let limit_reader = (reader as &'a mut dyn io::Read).take(result.compressed_size);
 
@@ -1115,17 +1763,25 @@ pub fn read_zipfile_from_stream<'a, R: io::Read>(
         result.last_modified_time,
         result.using_data_descriptor,
         limit_reader,
-        None,
-        None,
+        password.as_ref().map(|p| p.as_slice()),
+        result.aes_mode,
         #[cfg(feature = "aes-crypto")]
         result.compressed_size,
     )?
-    .unwrap();
+    .map_err(|_| ZipError::UnsupportedArchive("Invalid password"))?;
 
     Ok(Some(ZipFile {
         data: Cow::Owned(result),
         crypto_reader: None,
-        reader: make_reader(result_compression_method, result_crc32, crypto_reader),
+        reader: make_reader(
+            result_compression_method,
+            result_crc32,
+            crypto_reader,
+            #[cfg(feature = "zstd")]
+            None,
+        ),
+        #[cfg(feature = "zstd")]
+        zstd_dictionary: None,
     }))
 }
 
@@ -1164,6 +1820,103 @@ mod test {
         assert_eq!(reader.len(), 1);
     }
 
+    #[test]
+    fn zip_with_prefix() {
+        use super::ZipArchive;
+        use crate::zip::write::{FileOptions, ZipWriter};
+        use std::io::{self, Read, Write};
+
+        const PREFIX: &[u8] = b"#!/bin/sh\nexit 0\n";
+
+        let mut writer = ZipWriter::new_with_prefix(io::Cursor::new(Vec::new()), PREFIX).unwrap();
+        writer
+            .start_file("file.txt", FileOptions::default())
+            .unwrap();
+        writer.write_all(b"payload").unwrap();
+        let stub = writer.finish().unwrap().into_inner();
+        assert!(stub.starts_with(PREFIX));
+
+        // Data prepended to an existing archive is detected as leading junk instead
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        writer
+            .start_file("file.txt", FileOptions::default())
+            .unwrap();
+        writer.write_all(b"payload").unwrap();
+        let mut prepended = PREFIX.to_vec();
+        prepended.extend(writer.finish().unwrap().into_inner());
+
+        for (archive, offset) in [(stub, 0), (prepended, PREFIX.len())] {
+            let mut archive = ZipArchive::new(io::Cursor::new(archive)).unwrap();
+            assert_eq!(archive.offset(), offset as u64);
+            let mut contents = String::new();
+            archive
+                .by_name("file.txt")
+                .unwrap()
+                .read_to_string(&mut contents)
+                .unwrap();
+            assert_eq!(contents, "payload");
+        }
+    }
+
+    #[test]
+    fn error_context() {
+        use super::ZipArchive;
+        use crate::zip::result::ZipError;
+        use crate::zip::write::{FileOptions, ZipWriter};
+        use std::error::Error;
+        use std::io::{self, Write};
+
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        for name in ["first.txt", "second.txt"] {
+            writer.start_file(name, FileOptions::default()).unwrap();
+            writer.write_all(name.as_bytes()).unwrap();
+        }
+        let mut bytes = writer.finish().unwrap().into_inner();
+        let archive = ZipArchive::new(io::Cursor::new(bytes.clone())).unwrap();
+        let header_start = archive.shared.files[1].header_start;
+        let central_header_start = archive.shared.files[1].central_header_start;
+
+        // Corrupt local header
+        let mut corrupt = bytes.clone();
+        corrupt[header_start as usize] = b'X';
+        let mut archive = ZipArchive::new(io::Cursor::new(corrupt)).unwrap();
+        let error = archive.by_index(1).err().unwrap();
+        assert!(matches!(
+            error.without_context(),
+            ZipError::InvalidArchive("Invalid local file header")
+        ));
+        let context = error.context().unwrap();
+        assert_eq!(context.entry_name.as_deref(), Some("second.txt"));
+        assert_eq!(context.offset, Some(header_start));
+        assert_eq!(context.field, Some("local file header signature"));
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "invalid Zip archive: Invalid local file header \
+                 (local file header signature at {header_start:#x}, for entry \"second.txt\")"
+            )
+        );
+
+        // Corrupt central directory header
+        let mut corrupt = bytes.clone();
+        corrupt[central_header_start as usize] = b'X';
+        let error = ZipArchive::new(io::Cursor::new(corrupt)).err().unwrap();
+        let context = error.context().unwrap();
+        assert_eq!(context.entry_index, Some(1));
+        assert_eq!(context.offset, Some(central_header_start));
+        assert_eq!(context.field, Some("central directory header signature"));
+
+        // Local header offset beyond the end of the archive
+        let offset_field = central_header_start as usize + 42;
+        bytes[offset_field..offset_field + 4].copy_from_slice(&0x7fff_0000u32.to_le_bytes());
+        let mut archive = ZipArchive::new(io::Cursor::new(bytes)).unwrap();
+        let error = archive.by_index(1).err().unwrap();
+        assert!(matches!(error.without_context(), ZipError::Io(_)));
+        let source = error.source().unwrap().downcast_ref::<io::Error>().unwrap();
+        assert_eq!(source.kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(io::Error::from(error).kind(), io::ErrorKind::UnexpectedEof);
+    }
+
     #[test]
     fn zip_contents() {
         use super::ZipArchive;
@@ -1252,6 +2005,243 @@ mod test {
         }
     }
 
+    #[test]
+    fn zip_slice_stored() {
+        use super::ZipArchive;
+        use crate::zip::compression::CompressionMethod;
+        use crate::zip::write::{FileOptions, ZipWriter};
+        use std::io::{self, Write};
+
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+        writer.start_file("stored.txt", stored.clone()).unwrap();
+        writer.write_all(b"borrowed, not copied").unwrap();
+        writer
+            .start_file(
+                "encrypted.txt",
+                stored.with_deprecated_encryption(b"password"),
+            )
+            .unwrap();
+        writer.write_all(b"secret").unwrap();
+        let v = writer.finish().unwrap().into_inner();
+
+        let zip = ZipArchive::new(io::Cursor::new(v)).unwrap();
+        assert_eq!(
+            zip.by_name_slice("stored.txt").unwrap(),
+            b"borrowed, not copied"
+        );
+        assert!(zip.by_name_slice("encrypted.txt").is_err());
+        assert!(zip.by_name_slice("missing.txt").is_err());
+    }
+
+    #[test]
+    fn zip_slice_reader() {
+        use super::ZipArchive;
+        use crate::zip::compression::CompressionMethod;
+        use crate::zip::write::{FileOptions, ZipWriter};
+        use std::io::{self, Read, Write};
+
+        let contents = |name: &str| name.repeat(1000).into_bytes();
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+        writer.start_file("stored.txt", stored.clone()).unwrap();
+        writer.write_all(&contents("stored.txt")).unwrap();
+        writer
+            .start_file("compressed.txt", FileOptions::default())
+            .unwrap();
+        writer.write_all(&contents("compressed.txt")).unwrap();
+        writer
+            .start_file(
+                "encrypted.txt",
+                stored.with_deprecated_encryption(b"password"),
+            )
+            .unwrap();
+        writer.write_all(b"secret").unwrap();
+        let mut v = writer.finish().unwrap().into_inner();
+
+        let zip = ZipArchive::new(io::Cursor::new(v.clone())).unwrap();
+        // The archive is only borrowed, so the files can be read from several threads
+        std::thread::scope(|scope| {
+            for name in ["stored.txt", "compressed.txt"] {
+                let zip = &zip;
+                scope.spawn(move || {
+                    let mut file = zip.by_name_slice_reader(name).unwrap();
+                    assert_eq!(file.name(), name);
+                    let mut read = Vec::new();
+                    file.read_to_end(&mut read).unwrap();
+                    assert_eq!(read, contents(name));
+                });
+            }
+        });
+        assert!(zip.by_name_slice_reader("encrypted.txt").is_err());
+        assert!(zip.by_name_slice_reader("missing.txt").is_err());
+
+        // Corrupted contents fail the CRC32 check
+        let position = v
+            .windows(10)
+            .position(|window| window == b"stored.txt")
+            .unwrap();
+        let data_start = position + "stored.txt".len();
+        v[data_start] = b'S';
+        let zip = ZipArchive::new(io::Cursor::new(v)).unwrap();
+        let mut file = zip.by_name_slice_reader("stored.txt").unwrap();
+        assert!(file.read_to_end(&mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn zip_mixed_passwords() {
+        use super::ZipArchive;
+        use crate::zip::compression::CompressionMethod;
+        use crate::zip::manifest::EncryptionKind;
+        use crate::zip::password::Password;
+        use crate::zip::result::ZipError;
+        use crate::zip::write::{FileOptions, ZipWriter};
+        use std::collections::HashMap;
+        use std::io::{self, Read, Write};
+
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+        for (name, password) in [("first.txt", "one"), ("second.txt", "two")] {
+            writer
+                .start_file(
+                    name,
+                    stored
+                        .clone()
+                        .with_deprecated_encryption(password.as_bytes()),
+                )
+                .unwrap();
+            writer.write_all(name.as_bytes()).unwrap();
+        }
+        writer.start_file("plain.txt", stored).unwrap();
+        writer.write_all(b"plain.txt").unwrap();
+        let mut zip = ZipArchive::new(writer.finish().unwrap()).unwrap();
+
+        let mut passwords: HashMap<String, Password> = HashMap::new();
+        passwords.insert("first.txt".into(), Password::new(b"one".to_vec()));
+        passwords.insert("second.txt".into(), Password::new(b"two".to_vec()));
+        for i in 0..zip.len() {
+            let mut file = zip
+                .by_index_with_passwords(i, &mut passwords)
+                .unwrap()
+                .unwrap();
+            let mut contents = String::new();
+            file.read_to_string(&mut contents).unwrap();
+            assert_eq!(contents, file.name());
+        }
+
+        let mut asked = Vec::new();
+        let mut wrong = |name: &str, encryption| {
+            asked.push((name.to_owned(), encryption));
+            Some(Password::new(b"wrong".to_vec()))
+        };
+        assert!(zip
+            .by_name_with_passwords("first.txt", &mut wrong)
+            .unwrap()
+            .is_err());
+        assert_eq!(asked, [("first.txt".to_owned(), EncryptionKind::ZipCrypto)]);
+
+        match zip.by_name_with_passwords("second.txt", &mut |_: &str, _| None) {
+            Err(ZipError::UnsupportedArchive(ZipError::PASSWORD_REQUIRED)) => (),
+            _ => panic!("a missing password should be reported"),
+        };
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn extract_read_only() {
+        use super::ZipArchive;
+        use crate::zip::types::DosAttributes;
+        use crate::zip::write::{FileOptions, ZipWriter};
+        use std::io::{self, Write};
+        use std::os::unix::fs::PermissionsExt;
+
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        let attributes = DosAttributes {
+            read_only: true,
+            ..DosAttributes::default()
+        };
+        // Permissions given explicitly are still made read-only by the attribute
+        let options = FileOptions::default()
+            .unix_permissions(0o664)
+            .dos_attributes(attributes);
+        writer.start_file("read_only.txt", options).unwrap();
+        writer.write_all(b"do not touch").unwrap();
+        let mut archive = ZipArchive::new(writer.finish().unwrap()).unwrap();
+
+        let directory =
+            std::env::temp_dir().join(format!("zip_extract_read_only_{}", std::process::id()));
+        archive.extract(&directory).unwrap();
+        let path = directory.join("read_only.txt");
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(mode & 0o777, 0o444);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn extract_read_only_directory() {
+        use super::ZipArchive;
+        use crate::zip::write::{FileOptions, ZipWriter};
+        use std::io::{self, Write};
+        use std::os::unix::fs::PermissionsExt;
+
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        let options = FileOptions::default().unix_permissions(0o555);
+        writer.add_directory("locked/", options.clone()).unwrap();
+        writer.add_directory("locked/inner/", options).unwrap();
+        writer
+            .start_file("locked/inner/file.txt", FileOptions::default())
+            .unwrap();
+        writer.write_all(b"written after its directories").unwrap();
+        let mut archive = ZipArchive::new(writer.finish().unwrap()).unwrap();
+
+        let directory = std::env::temp_dir().join(format!(
+            "zip_extract_read_only_directory_{}",
+            std::process::id()
+        ));
+        let result = archive.extract(&directory);
+        let mode = |path: &str| {
+            let path = directory.join(path);
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+            mode & 0o777
+        };
+        let modes = (mode("locked"), mode("locked/inner"));
+        let contents = std::fs::read(directory.join("locked/inner/file.txt"));
+        std::fs::remove_dir_all(&directory).unwrap();
+        result.unwrap();
+        assert_eq!(modes, (0o555, 0o555));
+        assert_eq!(contents.unwrap(), b"written after its directories");
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn zip_open_mmap() {
+        use super::ZipArchive;
+        use crate::zip::write::{FileOptions, ZipWriter};
+        use std::io::{Read, Write};
+
+        let path = std::env::temp_dir().join(format!("zip_open_mmap_{}.zip", std::process::id()));
+        {
+            let mut writer = ZipWriter::new(std::fs::File::create(&path).unwrap());
+            writer
+                .start_file("hello.txt", FileOptions::default())
+                .unwrap();
+            writer.write_all(b"Hello, mapped world!").unwrap();
+            writer.finish().unwrap();
+        }
+
+        let mut zip = ZipArchive::open_mmap(&path).unwrap();
+        let mut contents = String::new();
+        zip.by_name("hello.txt")
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "Hello, mapped world!");
+        drop(zip);
+        std::fs::remove_file(&path).unwrap();
+    }
+
     /// test case to ensure we don't preemptively over allocate based on the
     /// declared number of files in the CDE of an invalid zip when the number of
     /// files declared is more than the alleged offset in the CDE
@@ -0,0 +1,272 @@
+use std::collections::BTreeMap;
+
+use super::ZipFileData;
+use crate::zip::types::DateTime;
+
+/// An index of the modification times and sizes of the entries of an archive, to query them
+/// without going through every entry.
+///
+/// Created by [`crate::zip::read::ZipArchive::metadata_index`] or
+/// [`crate::zip::read::LazyZipArchive::metadata_index`], from the central directory alone:
+/// no file data is read. The index does not borrow the archive, so it can be kept while
+/// files are opened by the index of their entries.
+#[derive(Debug, Clone)]
+pub struct MetadataIndex {
+    /// Entries in central directory order
+    entries: Vec<EntryMetadata>,
+    /// Positions in `entries`, sorted by modification time
+    by_time: Vec<usize>,
+    /// Positions in `entries` of the files, sorted by decreasing size
+    by_size: Vec<usize>,
+    directories: BTreeMap<String, DirectoryTotals>,
+}
+
+/// Metadata of a single entry in a [`MetadataIndex`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryMetadata {
+    /// Index of the entry in the archive, to open it with `by_index`
+    pub index: usize,
+    /// Name of the entry
+    pub name: String,
+    /// Last modified time, with a 2 second precision
+    pub last_modified: DateTime,
+    /// Size of the file when extracted
+    pub size: u64,
+    /// Size of the file in the archive
+    pub compressed_size: u64,
+    /// Whether the entry is a directory
+    pub is_dir: bool,
+}
+
+/// Totals of the files in a directory and its subdirectories, see
+/// [`MetadataIndex::directory_totals`]
+///
+/// The sizes come from the central directory, which can claim any size, so the totals stop
+/// at `u64::MAX` instead of overflowing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DirectoryTotals {
+    /// Number of files, directories excluded
+    pub files: u64,
+    /// Total size of the files when extracted
+    pub size: u64,
+    /// Total size of the files in the archive
+    pub compressed_size: u64,
+}
+
+impl EntryMetadata {
+    pub(crate) fn new(index: usize, data: &ZipFileData) -> EntryMetadata {
+        EntryMetadata {
+            index,
+            name: data.file_name.clone(),
+            last_modified: data.last_modified_time,
+            size: data.uncompressed_size,
+            compressed_size: data.compressed_size,
+            is_dir: data.file_name.ends_with('/') || data.file_name.ends_with('\\'),
+        }
+    }
+}
+
+impl MetadataIndex {
+    pub(crate) fn new(entries: Vec<EntryMetadata>) -> MetadataIndex {
+        let mut by_time: Vec<usize> = (0..entries.len()).collect();
+        by_time.sort_by_key(|&position| entries[position].last_modified);
+        let mut by_size: Vec<usize> = (0..entries.len())
+            .filter(|&position| !entries[position].is_dir)
+            .collect();
+        by_size.sort_by(|&a, &b| entries[b].size.cmp(&entries[a].size));
+
+        let mut directories = BTreeMap::new();
+        directories.insert(String::new(), DirectoryTotals::default());
+        for entry in &entries {
+            // The root and every directory enclosing the entry, as prefixes of its name
+            let parents = entry
+                .name
+                .match_indices('/')
+                .map(|(end, _)| &entry.name[..=end]);
+            for directory in std::iter::once("").chain(parents) {
+                let totals = directories.entry(directory.to_owned()).or_default();
+                if !entry.is_dir {
+                    totals.files = totals.files.saturating_add(1);
+                    totals.size = totals.size.saturating_add(entry.size);
+                    totals.compressed_size =
+                        totals.compressed_size.saturating_add(entry.compressed_size);
+                }
+            }
+        }
+
+        MetadataIndex {
+            entries,
+            by_time,
+            by_size,
+            directories,
+        }
+    }
+
+    /// Number of entries in the index
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the archive has no entries
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// All the entries, in central directory order
+    pub fn entries(&self) -> &[EntryMetadata] {
+        &self.entries
+    }
+
+    /// The entries last modified between `start` and `end`, both included, from the oldest to
+    /// the most recent
+    ///
+    /// Entries modified at the same time are in central directory order.
+    pub fn entries_between(
+        &self,
+        start: DateTime,
+        end: DateTime,
+    ) -> impl Iterator<Item = &EntryMetadata> {
+        let first = self
+            .by_time
+            .partition_point(|&position| self.entries[position].last_modified < start);
+        let last = self
+            .by_time
+            .partition_point(|&position| self.entries[position].last_modified <= end);
+        self.by_time[first..last.max(first)]
+            .iter()
+            .map(move |&position| &self.entries[position])
+    }
+
+    /// The files larger than `size` bytes when extracted, from the largest to the smallest
+    pub fn larger_than(&self, size: u64) -> impl Iterator<Item = &EntryMetadata> {
+        let end = self
+            .by_size
+            .partition_point(|&position| self.entries[position].size > size);
+        self.by_size[..end]
+            .iter()
+            .map(move |&position| &self.entries[position])
+    }
+
+    /// The `n` largest files when extracted, from the largest to the smallest
+    ///
+    /// Directories are left out, and files of the same size are in central directory order.
+    pub fn largest(&self, n: usize) -> impl Iterator<Item = &EntryMetadata> {
+        self.by_size
+            .iter()
+            .take(n)
+            .map(move |&position| &self.entries[position])
+    }
+
+    /// The totals of the files in every directory, including its subdirectories
+    ///
+    /// Directories are keyed by their path with a trailing `/`, and the root of the archive
+    /// by an empty string. Directories that only appear in the names of other files are
+    /// included, as are empty directories. Names are used as stored, without the
+    /// normalisation of [`crate::zip::read::ZipFile::enclosed_name`].
+    pub fn directory_totals(&self) -> &BTreeMap<String, DirectoryTotals> {
+        &self.directories
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DirectoryTotals, EntryMetadata, MetadataIndex};
+    use crate::zip::compression::CompressionMethod;
+    use crate::zip::read::{LazyZipArchive, ZipArchive};
+    use crate::zip::types::DateTime;
+    use crate::zip::write::{FileOptions, ZipWriter};
+    use std::io::{self, Write};
+
+    fn day(day: u8, hour: u8) -> DateTime {
+        DateTime::from_date_and_time(2023, 5, day, hour, 0, 0).unwrap()
+    }
+
+    fn sample_archive() -> Vec<u8> {
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        let files = [
+            ("logs/", day(1, 0), 0),
+            ("logs/b.log", day(3, 12), 300),
+            ("logs/old/a.log", day(1, 8), 100),
+            ("readme.txt", day(2, 9), 50),
+            ("logs/c.log", day(2, 9), 300),
+            ("empty/", day(4, 0), 0),
+        ];
+        for (name, modified, size) in files {
+            let options = FileOptions::default()
+                .compression_method(CompressionMethod::Stored)
+                .last_modified_time(modified);
+            if name.ends_with('/') {
+                writer.add_directory(name, options).unwrap();
+            } else {
+                writer.start_file(name, options).unwrap();
+                writer.write_all(&vec![b'x'; size]).unwrap();
+            }
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn names<'a>(entries: impl Iterator<Item = &'a EntryMetadata>) -> Vec<&'a str> {
+        entries.map(|entry| entry.name.as_str()).collect()
+    }
+
+    #[test]
+    fn metadata_queries() {
+        let bytes = sample_archive();
+        let archive = ZipArchive::new(io::Cursor::new(bytes.clone())).unwrap();
+        let index = archive.metadata_index();
+        let lazy = LazyZipArchive::new(io::Cursor::new(bytes)).unwrap();
+        assert_eq!(lazy.metadata_index().unwrap().entries(), index.entries());
+        assert_eq!(index.len(), 6);
+
+        assert_eq!(
+            names(index.entries_between(day(1, 8), day(3, 0))),
+            ["logs/old/a.log", "readme.txt", "logs/c.log"]
+        );
+        assert_eq!(
+            names(index.entries_between(day(3, 0), day(1, 0))),
+            Vec::<&str>::new()
+        );
+        assert_eq!(names(index.larger_than(100)), ["logs/b.log", "logs/c.log"]);
+        assert_eq!(
+            names(index.largest(3)),
+            ["logs/b.log", "logs/c.log", "logs/old/a.log"]
+        );
+        assert_eq!(index.largest(10).count(), 4);
+
+        let totals = index.directory_totals();
+        assert_eq!(
+            totals.keys().map(String::as_str).collect::<Vec<_>>(),
+            ["", "empty/", "logs/", "logs/old/"]
+        );
+        let total = |files, size| DirectoryTotals {
+            files,
+            size,
+            compressed_size: size,
+        };
+        assert_eq!(totals[""], total(4, 750));
+        assert_eq!(totals["empty/"], total(0, 0));
+        assert_eq!(totals["logs/"], total(3, 700));
+        assert_eq!(totals["logs/old/"], total(1, 100));
+    }
+
+    #[test]
+    fn directory_totals_saturate() {
+        let entry = |index, name: &str| EntryMetadata {
+            index,
+            name: name.to_owned(),
+            last_modified: day(1, 0),
+            size: u64::MAX - 1,
+            compressed_size: u64::MAX / 2 + 1,
+            is_dir: false,
+        };
+        let index = MetadataIndex::new(vec![entry(0, "huge/a.bin"), entry(1, "huge/b.bin")]);
+        assert_eq!(
+            index.directory_totals()["huge/"],
+            DirectoryTotals {
+                files: 2,
+                size: u64::MAX,
+                compressed_size: u64::MAX,
+            }
+        );
+    }
+}
@@ -0,0 +1,446 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io::{self, Read, Seek};
+
+use byteorder::{ByteOrder, LittleEndian};
+
+#[cfg(feature = "zstd")]
+use super::load_zstd_dictionary;
+use super::{
+    central_header_to_zip_file_inner, open_file, open_raw_file, unsupported_zip_error,
+    EntryMetadata, MetadataIndex, ZipArchive, ZipError, ZipFile, ZipFileData, ZipResult,
+};
+use crate::zip::encoding::Encoding;
+use crate::zip::result::InvalidPassword;
+use crate::zip::spec;
+use crate::zip::zstd_dictionary;
+#[cfg(feature = "zstd")]
+use std::sync::Arc;
+
+/// Size of the fixed part of a central directory header, signature included
+const CENTRAL_HEADER_SIZE: usize = 46;
+
+/// ZIP archive reader that parses the central directory on demand
+///
+/// [`ZipArchive::new`] parses every central directory record up front and indexes the names in
+/// a hash map, which takes a while and a lot of memory for archives with millions of files.
+/// This reader instead keeps the raw central directory in memory, along with the offset of
+/// each record and the record indices sorted by name. Records are parsed when a file is
+/// opened, so opening the same file twice parses its record twice.
+///
+/// ```no_run
+/// use std::io::prelude::*;
+/// fn print_file(reader: impl Read + Seek, name: &str) -> zip::result::ZipResult<()> {
+///     let mut zip = zip::read::LazyZipArchive::new(reader)?;
+///     let mut file = zip.by_name(name)?;
+///     std::io::copy(&mut file, &mut std::io::stdout())?;
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct LazyZipArchive<R> {
+    reader: R,
+    /// Raw central directory
+    directory: Vec<u8>,
+    /// Position of the central directory in the archive
+    directory_start: u64,
+    /// Offset of each record in `directory`
+    records: Vec<u32>,
+    /// Indices of the records, sorted by raw file name
+    sorted: Vec<u32>,
+    /// Files whose name is neither UTF-8 nor ASCII, and thus differs from its raw bytes
+    legacy_names: HashMap<String, u32>,
+    offset: u64,
+    comment: Vec<u8>,
+    /// Encoding of the names that are not marked as UTF-8, never `Auto`
+    name_encoding: Encoding,
+    /// The zstd dictionaries loaded so far, by id
+    #[cfg(feature = "zstd")]
+    zstd_dictionaries: HashMap<u32, Arc<[u8]>>,
+}
+
+impl<R: Read + Seek> LazyZipArchive<R> {
+    /// Read a ZIP archive, keeping its central directory for later
+    ///
+    /// This uses the central directory record of the ZIP file, and ignores local file headers
+    pub fn new(reader: R) -> ZipResult<LazyZipArchive<R>> {
+        Self::with_name_encoding(reader, Encoding::Cp437)
+    }
+
+    /// Read a ZIP archive like [`LazyZipArchive::new`], decoding the file names and comments
+    /// that are not marked as UTF-8 with `encoding` instead of CP437
+    ///
+    /// With [`Encoding::Auto`], the encoding is detected from the names of all the files, as
+    /// with [`ZipArchive::with_name_encoding`].
+    pub fn with_name_encoding(mut reader: R, encoding: Encoding) -> ZipResult<LazyZipArchive<R>> {
+        let (footer, cde_start_pos) = spec::CentralDirectoryEnd::find_and_parse(&mut reader)?;
+
+        if !footer.record_too_small() && footer.disk_number != footer.disk_with_central_directory {
+            return unsupported_zip_error("Support for multi-disk files is not implemented");
+        }
+
+        let (archive_offset, directory_start, number_of_files) =
+            ZipArchive::get_directory_counts(&mut reader, &footer, cde_start_pos)?;
+
+        // If the parsed number of files is greater than the offset then
+        // something fishy is going on and we shouldn't trust number_of_files.
+        let file_capacity = if number_of_files > cde_start_pos as usize {
+            0
+        } else {
+            number_of_files
+        };
+
+        if reader.seek(io::SeekFrom::Start(directory_start)).is_err() {
+            return Err(ZipError::InvalidArchive(
+                "Could not seek to start of central directory",
+            ));
+        }
+
+        let mut directory = Vec::new();
+        let mut records = Vec::with_capacity(file_capacity);
+        for index in 0..number_of_files {
+            let start = directory.len();
+            let header_start = directory_start + start as u64;
+            records.push(
+                u32::try_from(start)
+                    .map_err(|_| ZipError::UnsupportedArchive("Central directory is too large"))?,
+            );
+
+            directory.resize(start + CENTRAL_HEADER_SIZE, 0);
+            reader.read_exact(&mut directory[start..]).map_err(|e| {
+                ZipError::from(e)
+                    .with_offset(header_start, "central directory header")
+                    .with_entry_index(index)
+            })?;
+            let header = &directory[start..];
+            if LittleEndian::read_u32(header) != spec::CENTRAL_DIRECTORY_HEADER_SIGNATURE {
+                return Err(ZipError::InvalidArchive("Invalid Central Directory header")
+                    .with_offset(header_start, "central directory header signature")
+                    .with_entry_index(index));
+            }
+            let variable_length = LittleEndian::read_u16(&header[28..]) as usize
+                + LittleEndian::read_u16(&header[30..]) as usize
+                + LittleEndian::read_u16(&header[32..]) as usize;
+
+            let fixed_end = directory.len();
+            directory.resize(fixed_end + variable_length, 0);
+            reader
+                .read_exact(&mut directory[fixed_end..])
+                .map_err(|e| {
+                    ZipError::from(e)
+                        .with_offset(header_start, "central directory header")
+                        .with_entry_index(index)
+                })?;
+        }
+        directory.shrink_to_fit();
+
+        let mut archive = LazyZipArchive {
+            reader,
+            directory,
+            directory_start,
+            records,
+            sorted: Vec::new(),
+            legacy_names: HashMap::new(),
+            offset: archive_offset,
+            comment: footer.zip_file_comment,
+            name_encoding: encoding,
+            #[cfg(feature = "zstd")]
+            zstd_dictionaries: HashMap::new(),
+        };
+        if encoding == Encoding::Auto {
+            archive.name_encoding =
+                Encoding::detect((0..archive.len()).map(|index| archive.raw_name(index)));
+        }
+        archive.build_name_index();
+        Ok(archive)
+    }
+
+    fn build_name_index(&mut self) {
+        let mut sorted = Vec::with_capacity(self.records.len());
+        for index in 0..self.records.len() as u32 {
+            let raw = self.raw_name(index as usize);
+            if self.is_utf8(index as usize) || raw.is_ascii() {
+                sorted.push(index);
+            } else {
+                self.legacy_names
+                    .insert(self.name_encoding.decode(raw).into_owned(), index);
+            }
+        }
+        // The sort is stable, so the last of several files with the same name is looked up,
+        // as with `ZipArchive`.
+        sorted.sort_by(|&a, &b| self.raw_name(a as usize).cmp(self.raw_name(b as usize)));
+        self.sorted = sorted;
+    }
+
+    fn record(&self, index: usize) -> &[u8] {
+        &self.directory[self.records[index] as usize..]
+    }
+
+    fn is_utf8(&self, index: usize) -> bool {
+        LittleEndian::read_u16(&self.record(index)[8..]) & (1 << 11) != 0
+    }
+
+    fn raw_name(&self, index: usize) -> &[u8] {
+        let record = self.record(index);
+        let length = LittleEndian::read_u16(&record[28..]) as usize;
+        &record[CENTRAL_HEADER_SIZE..CENTRAL_HEADER_SIZE + length]
+    }
+
+    /// Parse the central directory record of a file.
+    pub(crate) fn file_data(&self, index: usize) -> ZipResult<ZipFileData> {
+        let start = *self.records.get(index).ok_or(ZipError::FileNotFound)? as usize;
+        let central_header_start = self.directory_start + start as u64;
+        // The signature was checked when the central directory was read
+        let mut record = io::Cursor::new(&self.directory[start + 4..]);
+        central_header_to_zip_file_inner(
+            &mut record,
+            self.offset,
+            central_header_start,
+            self.name_encoding,
+        )
+        .map_err(|e| {
+            e.with_offset(central_header_start, "central directory header")
+                .with_entry_index(index)
+        })
+    }
+
+    /// Number of files contained in this zip.
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Whether this zip archive contains no files
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the offset from the beginning of the underlying reader that this zip begins at, in bytes.
+    ///
+    /// Normally this value is zero, but if the zip has arbitrary data prepended to it, then this value will be the size
+    /// of that prepended data.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Get the comment of the zip archive.
+    pub fn comment(&self) -> &[u8] {
+        &self.comment
+    }
+
+    /// Get the name of a contained file by index, without parsing the rest of its record
+    pub fn name_for_index(&self, index: usize) -> Option<Cow<'_, str>> {
+        if index >= self.len() {
+            return None;
+        }
+        let raw = self.raw_name(index);
+        Some(match self.is_utf8(index) {
+            true => String::from_utf8_lossy(raw),
+            false => self.name_encoding.decode(raw),
+        })
+    }
+
+    /// Returns an iterator over all the file and directory names in this archive, in central
+    /// directory order.
+    pub fn file_names(&self) -> impl Iterator<Item = Cow<'_, str>> {
+        (0..self.len()).filter_map(move |index| self.name_for_index(index))
+    }
+
+    /// Get the index of a file entry by name, if it's present.
+    pub fn index_for_name(&self, name: &str) -> Option<usize> {
+        if let Some(index) = self.legacy_names.get(name) {
+            return Some(*index as usize);
+        }
+        let end = self
+            .sorted
+            .partition_point(|&index| self.raw_name(index as usize) <= name.as_bytes());
+        let index = *self.sorted[..end].last()? as usize;
+        match self.raw_name(index).cmp(name.as_bytes()) {
+            Ordering::Equal => Some(index),
+            _ => None,
+        }
+    }
+
+    /// Search for a file entry by name
+    pub fn by_name(&mut self, name: &str) -> ZipResult<ZipFile<'_>> {
+        let index = self.index_for_name(name).ok_or(ZipError::FileNotFound)?;
+        self.by_index(index)
+    }
+
+    /// Search for a file entry by name, decrypt with given password
+    ///
+    /// The same warnings as for [`ZipArchive::by_name_decrypt`] apply.
+    pub fn by_name_decrypt(
+        &mut self,
+        name: &str,
+        password: &[u8],
+    ) -> ZipResult<Result<ZipFile<'_>, InvalidPassword>> {
+        let index = self.index_for_name(name).ok_or(ZipError::FileNotFound)?;
+        self.by_index_decrypt(index, password)
+    }
+
+    /// Get a contained file by index
+    pub fn by_index(&mut self, file_number: usize) -> ZipResult<ZipFile<'_>> {
+        Ok(self
+            .by_index_with_optional_password(file_number, None)?
+            .unwrap())
+    }
+
+    /// Get a contained file by index, decrypt with given password
+    ///
+    /// The same warnings as for [`ZipArchive::by_index_decrypt`] apply.
+    pub fn by_index_decrypt(
+        &mut self,
+        file_number: usize,
+        password: &[u8],
+    ) -> ZipResult<Result<ZipFile<'_>, InvalidPassword>> {
+        self.by_index_with_optional_password(file_number, Some(password))
+    }
+
+    fn by_index_with_optional_password(
+        &mut self,
+        file_number: usize,
+        password: Option<&[u8]>,
+    ) -> ZipResult<Result<ZipFile<'_>, InvalidPassword>> {
+        let data = self.file_data(file_number)?;
+
+        #[cfg(feature = "zstd")]
+        let zstd_dictionary = match zstd_dictionary::used_by(&data) {
+            Some(id) => {
+                let entry = self
+                    .index_for_name(&zstd_dictionary::name(id))
+                    .map(|index| self.file_data(index))
+                    .transpose()?
+                    .map(Cow::Owned);
+                let dictionary =
+                    load_zstd_dictionary(id, entry, &mut self.reader, &mut self.zstd_dictionaries)
+                        .map_err(|e| e.with_entry_name(&data.file_name))?;
+                Some(dictionary)
+            }
+            None => None,
+        };
+
+        open_file(
+            Cow::Owned(data),
+            &mut self.reader,
+            password,
+            #[cfg(feature = "zstd")]
+            zstd_dictionary,
+        )
+    }
+
+    /// Index the modification times and sizes of the entries, like
+    /// [`ZipArchive::metadata_index`]
+    ///
+    /// Every central directory record is parsed, but no file data is read.
+    pub fn metadata_index(&self) -> ZipResult<MetadataIndex> {
+        let mut entries = Vec::new();
+        for index in 0..self.len() {
+            let data = self.file_data(index)?;
+            if !zstd_dictionary::is_dictionary(&data) {
+                entries.push(EntryMetadata::new(index, &data));
+            }
+        }
+        Ok(MetadataIndex::new(entries))
+    }
+
+    /// Get a contained file by index without decompressing it
+    pub fn by_index_raw(&mut self, file_number: usize) -> ZipResult<ZipFile<'_>> {
+        let data = self.file_data(file_number)?;
+        open_raw_file(Cow::Owned(data), &mut self.reader)
+    }
+
+    /// Unwrap and return the inner reader object
+    ///
+    /// The position of the reader is undefined.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::LazyZipArchive;
+    use crate::zip::compression::CompressionMethod;
+    use crate::zip::read::ZipArchive;
+    use crate::zip::result::ZipError;
+    use crate::zip::write::{FileOptions, ZipWriter};
+    use std::io::{self, Read, Write};
+
+    fn sample_archive() -> Vec<u8> {
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+        for name in ["b.txt", "a.txt", "dir/ünïcödé.txt", "c.txt"] {
+            writer.start_file(name, options.clone()).unwrap();
+            writer.write_all(name.as_bytes()).unwrap();
+        }
+        writer.add_directory("dir", options.clone()).unwrap();
+        writer
+            .start_file(
+                "secret.txt",
+                options.with_deprecated_encryption(b"password"),
+            )
+            .unwrap();
+        writer.write_all(b"secret").unwrap();
+        writer.set_comment("lazy");
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn lazy_matches_eager() {
+        let bytes = sample_archive();
+        let mut eager = ZipArchive::new(io::Cursor::new(bytes.clone())).unwrap();
+        let mut lazy = LazyZipArchive::new(io::Cursor::new(bytes)).unwrap();
+
+        assert_eq!(lazy.len(), eager.len());
+        assert_eq!(lazy.comment(), b"lazy");
+        for i in 0..eager.len() - 1 {
+            let expected = eager.by_index(i).unwrap();
+            let mut file = lazy.by_index(i).unwrap();
+            assert_eq!(file.name(), expected.name());
+            assert_eq!(file.central_header_start(), expected.central_header_start());
+            assert_eq!(file.header_start(), expected.header_start());
+
+            let mut contents = String::new();
+            file.read_to_string(&mut contents).unwrap();
+            if file.is_file() {
+                assert_eq!(contents, file.name());
+            }
+        }
+    }
+
+    #[test]
+    fn lazy_lookup_by_name() {
+        let mut lazy = LazyZipArchive::new(io::Cursor::new(sample_archive())).unwrap();
+
+        let names: Vec<_> = lazy.file_names().map(|name| name.into_owned()).collect();
+        assert_eq!(
+            names,
+            [
+                "b.txt",
+                "a.txt",
+                "dir/ünïcödé.txt",
+                "c.txt",
+                "dir/",
+                "secret.txt"
+            ]
+        );
+        assert_eq!(lazy.index_for_name("a.txt"), Some(1));
+        assert_eq!(lazy.index_for_name("dir/ünïcödé.txt"), Some(2));
+        assert_eq!(lazy.index_for_name("d.txt"), None);
+        assert_eq!(lazy.index_for_name(""), None);
+        assert!(matches!(
+            lazy.by_name("missing.txt"),
+            Err(ZipError::FileNotFound)
+        ));
+
+        let mut contents = String::new();
+        lazy.by_name_decrypt("secret.txt", b"password")
+            .unwrap()
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "secret");
+    }
+}
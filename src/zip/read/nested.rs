@@ -0,0 +1,275 @@
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::{find_data_start, ZipArchive, ZipError, ZipResult};
+use crate::zip::compression::CompressionMethod;
+
+/// Separator between the name of an archive and a path inside it, see [`open_nested_path`]
+pub const NESTED_PATH_SEPARATOR: &str = "!/";
+
+/// Compressed inner archives larger than this are buffered to a temporary file
+const MEMORY_LIMIT: u64 = 64 << 20;
+
+/// A reader that can be boxed to open archives nested at any depth
+pub trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
+/// The contents of an archive nested in another archive
+///
+/// Created by [`ZipArchive::open_nested`] and [`ZipArchive::into_nested`]. `Stored` inner
+/// archives are read directly from the byte range of the outer archive. Compressed ones are
+/// decompressed up front, to memory or to a temporary file when they are larger than 64 MiB.
+pub struct NestedReader<R> {
+    inner: Inner<R>,
+}
+
+enum Inner<R> {
+    Range {
+        reader: R,
+        start: u64,
+        len: u64,
+        pos: u64,
+    },
+    Memory(io::Cursor<Vec<u8>>),
+    File(TempFile),
+}
+
+/// Where to read an inner archive from, found before borrowing or taking the outer reader
+pub(crate) enum NestedSource {
+    Range { start: u64, len: u64 },
+    Memory(io::Cursor<Vec<u8>>),
+    File(TempFile),
+}
+
+impl NestedSource {
+    /// Find the data of the file `index` of `archive`, decompressing it if needed
+    pub(crate) fn new<R: Read + Seek>(
+        archive: &mut ZipArchive<R>,
+        index: usize,
+    ) -> ZipResult<NestedSource> {
+        let data = archive
+            .shared
+            .files
+            .get(index)
+            .ok_or(ZipError::FileNotFound)?;
+        if data.compression_method == CompressionMethod::Stored && !data.encrypted {
+            let start = find_data_start(data, &mut archive.reader)?;
+            return Ok(NestedSource::Range {
+                start,
+                len: data.compressed_size,
+            });
+        }
+
+        let size = data.uncompressed_size;
+        let mut file = archive.by_index(index)?;
+        if size <= MEMORY_LIMIT {
+            let mut buffer = Vec::with_capacity(size as usize);
+            file.read_to_end(&mut buffer)?;
+            Ok(NestedSource::Memory(io::Cursor::new(buffer)))
+        } else {
+            let mut temp = TempFile::new()?;
+            io::copy(&mut file, &mut temp.file)?;
+            Ok(NestedSource::File(temp))
+        }
+    }
+
+    pub(crate) fn into_reader<R: Seek>(self, reader: R) -> ZipResult<NestedReader<R>> {
+        let inner = match self {
+            NestedSource::Range { start, len } => Inner::Range {
+                reader,
+                start,
+                len,
+                pos: 0,
+            },
+            NestedSource::Memory(cursor) => Inner::Memory(cursor),
+            NestedSource::File(mut temp) => {
+                temp.file.seek(SeekFrom::Start(0))?;
+                Inner::File(temp)
+            }
+        };
+        Ok(NestedReader { inner })
+    }
+}
+
+impl<R: Read + Seek> Read for NestedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match &mut self.inner {
+            Inner::Range {
+                reader,
+                start,
+                len,
+                pos,
+            } => {
+                let remaining = len.saturating_sub(*pos);
+                let max = (buf.len() as u64).min(remaining) as usize;
+                if max == 0 {
+                    return Ok(0);
+                }
+                reader.seek(SeekFrom::Start(*start + *pos))?;
+                let count = reader.read(&mut buf[..max])?;
+                *pos += count as u64;
+                Ok(count)
+            }
+            Inner::Memory(cursor) => cursor.read(buf),
+            Inner::File(temp) => temp.file.read(buf),
+        }
+    }
+}
+
+impl<R: Read + Seek> Seek for NestedReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match &mut self.inner {
+            Inner::Range {
+                len, pos: current, ..
+            } => {
+                let target = match pos {
+                    SeekFrom::Start(offset) => Some(offset),
+                    SeekFrom::End(offset) => len.checked_add_signed(offset),
+                    SeekFrom::Current(offset) => current.checked_add_signed(offset),
+                };
+                *current = target.ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "invalid seek to a negative position",
+                    )
+                })?;
+                Ok(*current)
+            }
+            Inner::Memory(cursor) => cursor.seek(pos),
+            Inner::File(temp) => temp.file.seek(pos),
+        }
+    }
+}
+
+/// A file in the temporary directory, removed when dropped
+pub(crate) struct TempFile {
+    file: fs::File,
+    path: PathBuf,
+}
+
+impl TempFile {
+    fn new() -> io::Result<TempFile> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        loop {
+            let path = std::env::temp_dir().join(format!(
+                "nested-zip-{}-{}",
+                std::process::id(),
+                COUNTER.fetch_add(1, Ordering::Relaxed)
+            ));
+            let mut options = fs::OpenOptions::new();
+            options.read(true).write(true).create_new(true);
+            // The decompressed archive is only for this process
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::OpenOptionsExt;
+                options.mode(0o600);
+            }
+            match options.open(&path) {
+                Ok(file) => return Ok(TempFile { file, path }),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Open a file in nested archives, with a path like `outer.zip!/inner.zip!/file.txt`
+///
+/// The path before the first [`NESTED_PATH_SEPARATOR`] is a path on the filesystem, and is
+/// resolved with [`ZipArchive::into_nested_path`]. Returns the innermost archive and the
+/// name of the file in it, which is empty when the path ends with an archive.
+pub fn open_nested_path(path: &str) -> ZipResult<(ZipArchive<Box<dyn ReadSeek>>, String)> {
+    let (outer, rest) = match path.find(NESTED_PATH_SEPARATOR) {
+        Some(end) => (&path[..end], &path[end + NESTED_PATH_SEPARATOR.len()..]),
+        None => (path.strip_suffix('!').unwrap_or(path), ""),
+    };
+    let file = fs::File::open(Path::new(outer))?;
+    ZipArchive::new(file)?.into_nested_path(rest)
+}
+
+#[cfg(test)]
+mod test {
+    use super::open_nested_path;
+    use crate::zip::compression::CompressionMethod;
+    use crate::zip::read::ZipArchive;
+    use crate::zip::write::{FileOptions, ZipWriter};
+    use std::io::{self, Read, Write};
+
+    fn archive(name: &str, contents: &[u8], method: CompressionMethod) -> Vec<u8> {
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        let options = FileOptions::default().compression_method(method);
+        writer.start_file("padding.txt", options.clone()).unwrap();
+        writer
+            .write_all(b"some data before the nested archive")
+            .unwrap();
+        writer.start_file(name, options).unwrap();
+        writer.write_all(contents).unwrap();
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn open_nested() {
+        let inner = archive("file.txt", b"hello nested", CompressionMethod::Deflated);
+        for &method in &[CompressionMethod::Stored, CompressionMethod::Deflated] {
+            let outer = archive("inner.zip", &inner, method);
+            let mut archive = ZipArchive::new(io::Cursor::new(outer)).unwrap();
+            let mut nested = archive.open_nested("inner.zip").unwrap();
+            let mut contents = String::new();
+            nested
+                .by_name("file.txt")
+                .unwrap()
+                .read_to_string(&mut contents)
+                .unwrap();
+            assert_eq!(contents, "hello nested");
+            drop(nested);
+            assert!(archive.open_nested("padding.txt").is_err());
+        }
+    }
+
+    #[test]
+    fn nested_path() {
+        let inner = archive("file.txt", b"hello nested", CompressionMethod::Stored);
+        let middle = archive("inner.zip", &inner, CompressionMethod::Deflated);
+        let outer = archive("middle.zip", &middle, CompressionMethod::Stored);
+
+        let (mut archive, name) = ZipArchive::new(io::Cursor::new(outer.clone()))
+            .unwrap()
+            .into_nested_path("middle.zip!/inner.zip!/file.txt")
+            .unwrap();
+        assert_eq!(name, "file.txt");
+        let mut contents = String::new();
+        archive
+            .by_name(&name)
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "hello nested");
+
+        let path = std::env::temp_dir().join(format!("nested-path-{}.zip", std::process::id()));
+        std::fs::write(&path, &outer).unwrap();
+        let (archive, name) =
+            open_nested_path(&format!("{}!/middle.zip!/inner.zip!/", path.display())).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(name, "");
+        assert!(archive.file_names().any(|name| name == "file.txt"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn temp_file_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = super::TempFile::new().unwrap();
+        let mode = temp.file.metadata().unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
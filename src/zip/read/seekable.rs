@@ -0,0 +1,533 @@
+use std::borrow::Cow;
+use std::io::{self, Read, Seek, SeekFrom};
+
+use super::{find_data_start, CompressionMethod, ZipError, ZipFileData, ZipResult};
+
+#[cfg(feature = "zstd")]
+use byteorder::{LittleEndian, ReadBytesExt};
+#[cfg(feature = "zstd")]
+use zstd::stream::read::Decoder as ZstdDecoder;
+
+/// A reader for a single file in a [`super::ZipArchive`] that supports seeking.
+///
+/// Created by [`super::ZipArchive::by_name_seekable`] and
+/// [`super::ZipArchive::by_index_seekable`].
+pub struct ZipFileSeek<'a, R: Read> {
+    data: Cow<'a, ZipFileData>,
+    reader: SeekableReader<'a, R>,
+    pos: u64,
+}
+
+enum SeekableReader<'a, R: Read> {
+    Stored {
+        reader: &'a mut R,
+        data_start: u64,
+    },
+    #[cfg(feature = "zstd")]
+    Zstd(ZstdFrameReader<'a, R>),
+}
+
+impl<'a, R: Read + Seek> ZipFileSeek<'a, R> {
+    pub(crate) fn new(data: &'a ZipFileData, reader: &'a mut R) -> ZipResult<Self> {
+        if data.encrypted {
+            return Err(ZipError::UnsupportedArchive(
+                "Encrypted files cannot be read with seeking",
+            ));
+        }
+        let data_start = find_data_start(data, reader)?;
+
+        let reader = match data.compression_method {
+            CompressionMethod::Stored => {
+                reader.seek(SeekFrom::Start(data_start))?;
+                SeekableReader::Stored { reader, data_start }
+            }
+            #[cfg(feature = "zstd")]
+            CompressionMethod::Zstd => {
+                let table = ZstdSeekTable::parse(reader, data_start, data.compressed_size)?;
+                if table.decompressed_size() != data.uncompressed_size {
+                    return Err(ZipError::InvalidArchive(
+                        "Zstd seek table does not match the file size",
+                    ));
+                }
+                SeekableReader::Zstd(ZstdFrameReader {
+                    table,
+                    data_start,
+                    reader: Some(reader),
+                    decoder: None,
+                })
+            }
+            _ => {
+                return Err(ZipError::UnsupportedArchive(
+                    "Compression method does not support seeking",
+                ))
+            }
+        };
+
+        Ok(ZipFileSeek {
+            data: Cow::Borrowed(data),
+            reader,
+            pos: 0,
+        })
+    }
+}
+
+impl<'a, R: Read> ZipFileSeek<'a, R> {
+    /// Get the name of the file
+    ///
+    /// See [`super::ZipFile::name`] for the caveats of using this name as a path.
+    pub fn name(&self) -> &str {
+        &self.data.file_name
+    }
+
+    /// Get the compression method used to store the file
+    pub fn compression(&self) -> CompressionMethod {
+        self.data.compression_method
+    }
+
+    /// Get the size of the file, in bytes, in the archive
+    pub fn compressed_size(&self) -> u64 {
+        self.data.compressed_size
+    }
+
+    /// Get the size of the file, in bytes, when uncompressed
+    pub fn size(&self) -> u64 {
+        self.data.uncompressed_size
+    }
+
+    /// Get the starting offset of the data of the compressed file
+    pub fn data_start(&self) -> u64 {
+        self.data.data_start.load()
+    }
+}
+
+impl<'a, R: Read + Seek> Read for ZipFileSeek<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.data.uncompressed_size.saturating_sub(self.pos);
+        let len = remaining.min(buf.len() as u64) as usize;
+        if len == 0 {
+            return Ok(0);
+        }
+
+        let count = match &mut self.reader {
+            SeekableReader::Stored { reader, .. } => reader.read(&mut buf[..len])?,
+            #[cfg(feature = "zstd")]
+            SeekableReader::Zstd(reader) => reader.read_at(self.pos, &mut buf[..len])?,
+        };
+        self.pos += count as u64;
+        Ok(count)
+    }
+}
+
+impl<'a, R: Read + Seek> Seek for ZipFileSeek<'a, R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.data.uncompressed_size.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+        }
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+
+        if new_pos != self.pos {
+            match &mut self.reader {
+                SeekableReader::Stored { reader, data_start } => {
+                    let start = data_start.checked_add(new_pos).ok_or_else(|| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            "invalid seek to an overflowing position",
+                        )
+                    })?;
+                    reader.seek(SeekFrom::Start(start))?;
+                }
+                #[cfg(feature = "zstd")]
+                SeekableReader::Zstd(reader) => reader.reset()?,
+            }
+            self.pos = new_pos;
+        }
+        Ok(new_pos)
+    }
+}
+
+/// A frame listed in the seek table of a seekable Zstd stream.
+#[cfg(feature = "zstd")]
+#[derive(Debug, Clone, Copy)]
+struct ZstdFrame {
+    compressed_offset: u64,
+    compressed_size: u64,
+    decompressed_offset: u64,
+    decompressed_size: u64,
+}
+
+/// The seek table of a Zstd stream in the seekable format.
+#[cfg(feature = "zstd")]
+#[derive(Debug)]
+struct ZstdSeekTable {
+    frames: Vec<ZstdFrame>,
+}
+
+#[cfg(feature = "zstd")]
+impl ZstdSeekTable {
+    const SKIPPABLE_FRAME_MAGIC: u32 = 0x184D2A5E;
+    const SEEKABLE_MAGIC: u32 = 0x8F92EAB1;
+    const FOOTER_SIZE: u64 = 9;
+    const SKIPPABLE_HEADER_SIZE: u64 = 8;
+
+    /// Parse the seek table stored at the end of the `compressed_size` bytes at `data_start`.
+    fn parse<R: Read + Seek>(
+        reader: &mut R,
+        data_start: u64,
+        compressed_size: u64,
+    ) -> ZipResult<ZstdSeekTable> {
+        let no_table = ZipError::UnsupportedArchive("Zstd file has no seek table");
+        if compressed_size < Self::SKIPPABLE_HEADER_SIZE + Self::FOOTER_SIZE {
+            return Err(no_table);
+        }
+
+        let data_end = data_start
+            .checked_add(compressed_size)
+            .ok_or(ZipError::InvalidArchive("Zstd file data is too large"))?;
+        reader.seek(SeekFrom::Start(data_end - Self::FOOTER_SIZE))?;
+        let number_of_frames = reader.read_u32::<LittleEndian>()? as u64;
+        let descriptor = reader.read_u8()?;
+        if reader.read_u32::<LittleEndian>()? != Self::SEEKABLE_MAGIC {
+            return Err(no_table);
+        }
+        if descriptor & 0b0111_1100 != 0 {
+            return Err(ZipError::InvalidArchive(
+                "Zstd seek table uses reserved descriptor bits",
+            ));
+        }
+        let entry_size = if descriptor & 0x80 != 0 { 12 } else { 8 };
+
+        let table_size = number_of_frames
+            .checked_mul(entry_size)
+            .and_then(|size| size.checked_add(Self::FOOTER_SIZE))
+            .filter(|size| size + Self::SKIPPABLE_HEADER_SIZE <= compressed_size)
+            .ok_or(ZipError::InvalidArchive("Zstd seek table is too large"))?;
+        let table_start = data_end - table_size - Self::SKIPPABLE_HEADER_SIZE;
+        reader.seek(SeekFrom::Start(table_start))?;
+        if reader.read_u32::<LittleEndian>()? != Self::SKIPPABLE_FRAME_MAGIC
+            || reader.read_u32::<LittleEndian>()? as u64 != table_size
+        {
+            return Err(ZipError::InvalidArchive("Invalid Zstd seek table frame"));
+        }
+
+        let mut frames = Vec::with_capacity(number_of_frames as usize);
+        let mut compressed_offset = 0;
+        let mut decompressed_offset = 0;
+        for _ in 0..number_of_frames {
+            let compressed_size = reader.read_u32::<LittleEndian>()? as u64;
+            let decompressed_size = reader.read_u32::<LittleEndian>()? as u64;
+            if entry_size == 12 {
+                let _checksum = reader.read_u32::<LittleEndian>()?;
+            }
+            frames.push(ZstdFrame {
+                compressed_offset,
+                compressed_size,
+                decompressed_offset,
+                decompressed_size,
+            });
+            compressed_offset += compressed_size;
+            decompressed_offset += decompressed_size;
+        }
+
+        if compressed_offset != table_start - data_start {
+            return Err(ZipError::InvalidArchive(
+                "Zstd seek table does not match the compressed data",
+            ));
+        }
+
+        Ok(ZstdSeekTable { frames })
+    }
+
+    fn decompressed_size(&self) -> u64 {
+        self.frames.last().map_or(0, |frame| {
+            frame.decompressed_offset + frame.decompressed_size
+        })
+    }
+
+    /// Find the frame containing the decompressed byte at `pos`.
+    fn frame_at(&self, pos: u64) -> Option<&ZstdFrame> {
+        let index = self
+            .frames
+            .partition_point(|frame| frame.decompressed_offset + frame.decompressed_size <= pos);
+        self.frames.get(index)
+    }
+}
+
+#[cfg(feature = "zstd")]
+struct ZstdFrameReader<'a, R: Read> {
+    table: ZstdSeekTable,
+    data_start: u64,
+    // Exactly one of `reader` and `decoder` holds the underlying reader.
+    reader: Option<&'a mut R>,
+    decoder: Option<ZstdDecoder<'static, io::BufReader<io::Take<&'a mut R>>>>,
+}
+
+#[cfg(feature = "zstd")]
+impl<'a, R: Read + Seek> ZstdFrameReader<'a, R> {
+    /// Read decompressed data, `pos` being the current position in the decompressed file.
+    fn read_at(&mut self, pos: u64, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if let Some(decoder) = &mut self.decoder {
+                match decoder.read(buf)? {
+                    // The current frame is exhausted, continue with the next one.
+                    0 => self.reset()?,
+                    count => return Ok(count),
+                }
+            }
+
+            let frame = *self.table.frame_at(pos).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "position is past the last frame",
+                )
+            })?;
+            let reader = self
+                .reader
+                .take()
+                .ok_or_else(|| io::Error::other("The reader was lost by a previous failed read"))?;
+            let start = self
+                .data_start
+                .checked_add(frame.compressed_offset)
+                .ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidInput, "Zstd frame offset overflows")
+                });
+            if let Err(e) = start.and_then(|start| reader.seek(SeekFrom::Start(start))) {
+                self.reader = Some(reader);
+                return Err(e);
+            }
+            // This only fails when zstd cannot allocate its context, and drops the reader
+            let mut decoder = ZstdDecoder::new(reader.take(frame.compressed_size))?.single_frame();
+
+            // Skip to the requested position within the frame.
+            let skip = pos - frame.decompressed_offset;
+            let skipped = match io::copy(&mut (&mut decoder).take(skip), &mut io::sink()) {
+                Ok(skipped) => skipped,
+                Err(e) => {
+                    // Keep the reader, so that other frames can still be read
+                    self.reader = Some(decoder.finish().into_inner().into_inner());
+                    return Err(e);
+                }
+            };
+            self.decoder = Some(decoder);
+            if skipped != skip {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "Zstd frame is shorter than stated in the seek table",
+                ));
+            }
+        }
+    }
+
+    /// Drop the decoder of the current frame, if any.
+    fn reset(&mut self) -> io::Result<()> {
+        if let Some(decoder) = self.decoder.take() {
+            self.reader = Some(decoder.finish().into_inner().into_inner());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::zip::compression::CompressionMethod;
+    use crate::zip::read::ZipArchive;
+    use crate::zip::write::{FileOptions, ZipWriter};
+    use std::io::{self, Read, Seek, SeekFrom, Write};
+
+    fn archive_with(name: &str, content: &[u8], method: CompressionMethod) -> Vec<u8> {
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        let options = FileOptions::default().compression_method(method);
+        writer.start_file(name, options).unwrap();
+        writer.write_all(content).unwrap();
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn stored_seek_and_read() {
+        let content: Vec<u8> = (0..=255u8).cycle().take(10_000).collect();
+        let v = archive_with("data.bin", &content, CompressionMethod::Stored);
+        let mut archive = ZipArchive::new(io::Cursor::new(v)).unwrap();
+        let mut file = archive.by_name_seekable("data.bin").unwrap();
+        assert_eq!(file.size(), content.len() as u64);
+
+        let mut buf = [0u8; 16];
+        file.seek(SeekFrom::Start(5000)).unwrap();
+        file.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, content[5000..5016]);
+
+        file.seek(SeekFrom::Current(-32)).unwrap();
+        file.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, content[4984..5000]);
+
+        assert_eq!(file.seek(SeekFrom::End(-4)).unwrap(), 9996);
+        let mut tail = Vec::new();
+        file.read_to_end(&mut tail).unwrap();
+        assert_eq!(tail, content[9996..]);
+
+        assert!(file.seek(SeekFrom::Current(-20_000)).is_err());
+        assert_eq!(
+            file.seek(SeekFrom::Start(u64::MAX)).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+
+    #[cfg(any(
+        feature = "deflate",
+        feature = "deflate-miniz",
+        feature = "deflate-zlib"
+    ))]
+    #[test]
+    fn deflated_is_rejected() {
+        let v = archive_with("data.txt", b"hello", CompressionMethod::Deflated);
+        let mut archive = ZipArchive::new(io::Cursor::new(v)).unwrap();
+        assert!(archive.by_name_seekable("data.txt").is_err());
+    }
+
+    /// Compress `content` in the zstd seekable format, using frames of `frame_size` bytes.
+    #[cfg(feature = "zstd")]
+    fn zstd_seekable(content: &[u8], frame_size: usize) -> Vec<u8> {
+        use byteorder::{LittleEndian, WriteBytesExt};
+
+        let mut out = Vec::new();
+        let mut table = Vec::new();
+        for chunk in content.chunks(frame_size) {
+            let frame = zstd::encode_all(chunk, 3).unwrap();
+            table.write_u32::<LittleEndian>(frame.len() as u32).unwrap();
+            table.write_u32::<LittleEndian>(chunk.len() as u32).unwrap();
+            out.extend_from_slice(&frame);
+        }
+        let number_of_frames = (table.len() / 8) as u32;
+        out.write_u32::<LittleEndian>(0x184D2A5E).unwrap();
+        let seek_table_size = table.len() as u32 + 9;
+        out.write_u32::<LittleEndian>(seek_table_size).unwrap();
+        out.extend_from_slice(&table);
+        out.write_u32::<LittleEndian>(number_of_frames).unwrap();
+        out.write_u8(0).unwrap();
+        out.write_u32::<LittleEndian>(0x8F92EAB1).unwrap();
+        out
+    }
+
+    /// An archive with a single seekable Zstd file, `data.bin`
+    #[cfg(feature = "zstd")]
+    fn zstd_seekable_archive(content: &[u8]) -> Vec<u8> {
+        let compressed = zstd_seekable(content, 4096);
+
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        writer
+            .start_file(
+                "data.bin",
+                FileOptions::default().compression_method(CompressionMethod::Stored),
+            )
+            .unwrap();
+        writer.write_all(&compressed).unwrap();
+        let mut v = writer.finish().unwrap().into_inner();
+        // Relabel the stored entry as Zstd compressed, in the local and central headers, and
+        // fix up the uncompressed size.
+        let central = v.len() - 22 - 46 - "data.bin".len();
+        for header in [0, central] {
+            let method = header + if header == 0 { 8 } else { 10 };
+            v[method..method + 2].copy_from_slice(&93u16.to_le_bytes());
+            let size = method + 14;
+            v[size..size + 4].copy_from_slice(&(content.len() as u32).to_le_bytes());
+        }
+        v
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd_seekable_frames() {
+        let content: Vec<u8> = (0..50_000u32).flat_map(|i| i.to_le_bytes()).collect();
+        let v = zstd_seekable_archive(&content);
+        let mut archive = ZipArchive::new(io::Cursor::new(v)).unwrap();
+        let mut file = archive.by_name_seekable("data.bin").unwrap();
+        assert_eq!(file.size(), content.len() as u64);
+
+        let mut buf = [0u8; 64];
+        for &pos in &[150_000u64, 10, 4090, 199_936] {
+            file.seek(SeekFrom::Start(pos)).unwrap();
+            file.read_exact(&mut buf).unwrap();
+            assert_eq!(buf[..], content[pos as usize..pos as usize + 64]);
+        }
+
+        file.seek(SeekFrom::Start(0)).unwrap();
+        let mut all = Vec::new();
+        file.read_to_end(&mut all).unwrap();
+        assert_eq!(all, content);
+    }
+
+    /// A reader whose seeks fail while `fail` is set
+    #[cfg(feature = "zstd")]
+    struct FlakySeek {
+        inner: io::Cursor<Vec<u8>>,
+        fail: std::rc::Rc<std::cell::Cell<bool>>,
+    }
+
+    #[cfg(feature = "zstd")]
+    impl Read for FlakySeek {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.inner.read(buf)
+        }
+    }
+
+    #[cfg(feature = "zstd")]
+    impl Seek for FlakySeek {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            if self.fail.get() {
+                return Err(io::Error::other("seek failed"));
+            }
+            self.inner.seek(pos)
+        }
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd_seekable_after_failed_seek() {
+        let content: Vec<u8> = (0..50_000u32).flat_map(|i| i.to_le_bytes()).collect();
+        let fail = std::rc::Rc::new(std::cell::Cell::new(false));
+        let reader = FlakySeek {
+            inner: io::Cursor::new(zstd_seekable_archive(&content)),
+            fail: fail.clone(),
+        };
+        let mut archive = ZipArchive::new(reader).unwrap();
+        let mut file = archive.by_name_seekable("data.bin").unwrap();
+
+        let mut buf = [0u8; 64];
+        file.seek(SeekFrom::Start(100_000)).unwrap();
+        fail.set(true);
+        assert!(file.read_exact(&mut buf).is_err());
+        fail.set(false);
+        file.read_exact(&mut buf).unwrap();
+        assert_eq!(buf[..], content[100_000..100_064]);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd_seekable_after_corrupt_frame() {
+        let content: Vec<u8> = (0..50_000u32).flat_map(|i| i.to_le_bytes()).collect();
+        let mut v = zstd_seekable_archive(&content);
+        // Set the reserved bit of the header of the second frame
+        let second_frame = v
+            .windows(4)
+            .enumerate()
+            .filter(|(_, window)| *window == [0x28, 0xb5, 0x2f, 0xfd])
+            .nth(1)
+            .unwrap()
+            .0;
+        v[second_frame + 4] |= 0x08;
+        let mut archive = ZipArchive::new(io::Cursor::new(v)).unwrap();
+        let mut file = archive.by_name_seekable("data.bin").unwrap();
+
+        let mut buf = [0u8; 64];
+        file.seek(SeekFrom::Start(5000)).unwrap();
+        assert!(file.read_exact(&mut buf).is_err());
+        file.seek(SeekFrom::Start(100)).unwrap();
+        file.read_exact(&mut buf).unwrap();
+        assert_eq!(buf[..], content[100..164]);
+    }
+}
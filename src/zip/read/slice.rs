@@ -0,0 +1,124 @@
+use std::io::{self, Read};
+
+use super::{CompressionMethod, ZipFileData};
+use crate::zip::crc32::Crc32Reader;
+
+#[cfg(any(
+    feature = "deflate",
+    feature = "deflate-miniz",
+    feature = "deflate-zlib"
+))]
+use flate2::read::DeflateDecoder;
+
+#[cfg(feature = "bzip2")]
+use bzip2::read::BzDecoder;
+
+#[cfg(feature = "zstd")]
+use zstd::stream::read::Decoder as ZstdDecoder;
+
+/// A reader decompressing a file of an in-memory archive straight from the bytes of the
+/// archive.
+///
+/// Created by [`super::ZipArchive::by_name_slice_reader`] and
+/// [`super::ZipArchive::by_index_slice_reader`]. Unlike [`super::ZipFile`], it only borrows
+/// the archive immutably, so several files can be read at the same time, for instance from
+/// different threads. The CRC32 of the contents is checked once they are read entirely.
+pub struct ZipSliceReader<'a> {
+    data: &'a ZipFileData,
+    reader: SliceDecoder<'a>,
+}
+
+enum SliceDecoder<'a> {
+    Stored(Crc32Reader<&'a [u8]>),
+    #[cfg(any(
+        feature = "deflate",
+        feature = "deflate-miniz",
+        feature = "deflate-zlib"
+    ))]
+    Deflated(Crc32Reader<DeflateDecoder<&'a [u8]>>),
+    #[cfg(feature = "bzip2")]
+    Bzip2(Crc32Reader<BzDecoder<&'a [u8]>>),
+    #[cfg(feature = "zstd")]
+    Zstd(Crc32Reader<ZstdDecoder<'static, &'a [u8]>>),
+}
+
+impl<'a> ZipSliceReader<'a> {
+    /// Decompress `bytes`, the compressed data of the unencrypted file described by `data`
+    pub(crate) fn new(
+        data: &'a ZipFileData,
+        bytes: &'a [u8],
+        #[cfg(feature = "zstd")] zstd_dictionary: Option<&[u8]>,
+    ) -> io::Result<Self> {
+        let crc32 = data.crc32;
+        let reader = match data.compression_method {
+            CompressionMethod::Stored => {
+                SliceDecoder::Stored(Crc32Reader::new(bytes, crc32, false))
+            }
+            #[cfg(any(
+                feature = "deflate",
+                feature = "deflate-miniz",
+                feature = "deflate-zlib"
+            ))]
+            CompressionMethod::Deflated => {
+                SliceDecoder::Deflated(Crc32Reader::new(DeflateDecoder::new(bytes), crc32, false))
+            }
+            #[cfg(feature = "bzip2")]
+            CompressionMethod::Bzip2 => {
+                SliceDecoder::Bzip2(Crc32Reader::new(BzDecoder::new(bytes), crc32, false))
+            }
+            #[cfg(feature = "zstd")]
+            CompressionMethod::Zstd => {
+                let decoder = match zstd_dictionary {
+                    Some(dictionary) => ZstdDecoder::with_dictionary(bytes, dictionary)?,
+                    None => ZstdDecoder::with_buffer(bytes)?,
+                };
+                SliceDecoder::Zstd(Crc32Reader::new(decoder, crc32, false))
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "Compression method not supported",
+                ))
+            }
+        };
+        Ok(ZipSliceReader { data, reader })
+    }
+
+    /// Get the name of the file
+    pub fn name(&self) -> &str {
+        &self.data.file_name
+    }
+
+    /// Get the compression method used to store the file
+    pub fn compression(&self) -> CompressionMethod {
+        self.data.compression_method
+    }
+
+    /// Get the size of the file, in bytes, in the archive
+    pub fn compressed_size(&self) -> u64 {
+        self.data.compressed_size
+    }
+
+    /// Get the size of the file, in bytes, when uncompressed
+    pub fn size(&self) -> u64 {
+        self.data.uncompressed_size
+    }
+}
+
+impl<'a> Read for ZipSliceReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match &mut self.reader {
+            SliceDecoder::Stored(r) => r.read(buf),
+            #[cfg(any(
+                feature = "deflate",
+                feature = "deflate-miniz",
+                feature = "deflate-zlib"
+            ))]
+            SliceDecoder::Deflated(r) => r.read(buf),
+            #[cfg(feature = "bzip2")]
+            SliceDecoder::Bzip2(r) => r.read(buf),
+            #[cfg(feature = "zstd")]
+            SliceDecoder::Zstd(r) => r.read(buf),
+        }
+    }
+}
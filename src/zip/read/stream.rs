@@ -3,20 +3,38 @@ use std::io::{self, Read};
 use std::path::Path;
 
 use super::{
-    central_header_to_zip_file_inner, read_zipfile_from_stream, spec, ZipError, ZipFile,
-    ZipFileData, ZipResult,
+    central_header_to_zip_file_inner, read_zipfile_from_stream_with_name_encoding, spec, ZipError,
+    ZipFile, ZipFileData, ZipResult,
 };
 
+use crate::zip::encoding::Encoding;
+use crate::zip::manifest::EncryptionKind;
+use crate::zip::password::PasswordProvider;
+use crate::zip::types::DosAttributes;
+
 use byteorder::{LittleEndian, ReadBytesExt};
 
 /// Stream decoder for zip.
 #[derive(Debug)]
-pub struct ZipStreamReader<R>(R);
+pub struct ZipStreamReader<R> {
+    reader: R,
+    name_encoding: Encoding,
+}
 
 impl<R> ZipStreamReader<R> {
     /// Create a new ZipStreamReader
     pub fn new(reader: R) -> Self {
-        Self(reader)
+        Self::with_name_encoding(reader, Encoding::Cp437)
+    }
+
+    /// Create a new ZipStreamReader decoding the file names and comments that are not marked
+    /// as UTF-8 with `encoding` instead of CP437, see
+    /// [`super::read_zipfile_from_stream_with_name_encoding`]
+    pub fn with_name_encoding(reader: R, encoding: Encoding) -> Self {
+        Self {
+            reader,
+            name_encoding: encoding,
+        }
     }
 }
 
@@ -28,20 +46,42 @@ impl<R: Read> ZipStreamReader<R> {
         let central_header_start = 0;
 
         // Parse central header
-        let signature = self.0.read_u32::<LittleEndian>()?;
+        let signature = self.reader.read_u32::<LittleEndian>()?;
         if signature != spec::CENTRAL_DIRECTORY_HEADER_SIGNATURE {
             Ok(None)
         } else {
-            central_header_to_zip_file_inner(&mut self.0, archive_offset, central_header_start)
-                .map(ZipStreamFileMetadata)
-                .map(Some)
+            central_header_to_zip_file_inner(
+                &mut self.reader,
+                archive_offset,
+                central_header_start,
+                self.name_encoding,
+            )
+            .map(ZipStreamFileMetadata)
+            .map(Some)
         }
     }
 
     /// Iteraate over the stream and extract all file and their
     /// metadata.
-    pub fn visit<V: ZipStreamVisitor>(mut self, visitor: &mut V) -> ZipResult<()> {
-        while let Some(mut file) = read_zipfile_from_stream(&mut self.0)? {
+    pub fn visit<V: ZipStreamVisitor>(self, visitor: &mut V) -> ZipResult<()> {
+        self.visit_with_passwords(visitor, &mut |_: &str, _: EncryptionKind| None)
+    }
+
+    /// Iterate over the stream like [`ZipStreamReader::visit`], decrypting encrypted files
+    /// with the passwords given by `passwords`.
+    ///
+    /// A closure returning the same password for every file can be used when all the files
+    /// share it. The visit fails when the password of an encrypted file is missing or
+    /// invalid, see [`super::read_zipfile_from_stream_decrypt`].
+    pub fn visit_with_passwords<V: ZipStreamVisitor>(
+        mut self,
+        visitor: &mut V,
+        passwords: &mut dyn PasswordProvider,
+    ) -> ZipResult<()> {
+        let reader = &mut self.reader;
+        while let Some(mut file) =
+            read_zipfile_from_stream_with_name_encoding(reader, passwords, self.name_encoding)?
+        {
             visitor.visit_file(&mut file)?;
         }
 
@@ -58,6 +98,16 @@ impl<R: Read> ZipStreamReader<R> {
     /// Extraction is not atomic; If an error is encountered, some of the files
     /// may be left on disk.
     pub fn extract<P: AsRef<Path>>(self, directory: P) -> ZipResult<()> {
+        self.extract_with_passwords(directory, &mut |_: &str, _: EncryptionKind| None)
+    }
+
+    /// Extract a Zip archive into a directory like [`ZipStreamReader::extract`], decrypting
+    /// encrypted files with the passwords given by `passwords`.
+    pub fn extract_with_passwords<P: AsRef<Path>>(
+        self,
+        directory: P,
+        passwords: &mut dyn PasswordProvider,
+    ) -> ZipResult<()> {
         struct Extractor<'a>(&'a Path);
         impl ZipStreamVisitor for Extractor<'_> {
             fn visit_file(&mut self, file: &mut ZipFile<'_>) -> ZipResult<()> {
@@ -94,7 +144,7 @@ impl<R: Read> ZipStreamReader<R> {
                     let outpath = self.0.join(filepath);
 
                     use std::os::unix::fs::PermissionsExt;
-                    if let Some(mode) = metadata.unix_mode() {
+                    if let Some(mode) = metadata.0.extracted_unix_mode() {
                         fs::set_permissions(outpath, fs::Permissions::from_mode(mode))?;
                     }
                 }
@@ -103,7 +153,7 @@ impl<R: Read> ZipStreamReader<R> {
             }
         }
 
-        self.visit(&mut Extractor(directory.as_ref()))
+        self.visit_with_passwords(&mut Extractor(directory.as_ref()), passwords)
     }
 }
 
@@ -208,6 +258,11 @@ impl ZipStreamFileMetadata {
     pub fn unix_mode(&self) -> Option<u32> {
         self.0.unix_mode()
     }
+
+    /// Get the MS-DOS and Windows attributes of the file, if known
+    pub fn dos_attributes(&self) -> Option<DosAttributes> {
+        self.0.dos_attributes()
+    }
 }
 
 #[cfg(test)]
@@ -346,6 +401,63 @@ mod test {
         reader.visit(&mut V::default()).unwrap();
     }
 
+    #[test]
+    fn zip_read_streaming_encrypted() {
+        use crate::zip::password::Password;
+        use crate::zip::write::{FileOptions, ZipWriter};
+        use std::io::{Read, Write};
+
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        let options = FileOptions::default().with_deprecated_encryption(b"secret");
+        writer.start_file("secret.txt", options).unwrap();
+        writer.write_all(b"streamed secret").unwrap();
+        writer
+            .start_file("plain.txt", FileOptions::default())
+            .unwrap();
+        writer.write_all(b"plain").unwrap();
+        let bytes = writer.finish().unwrap().into_inner();
+
+        struct V(Vec<String>);
+        impl ZipStreamVisitor for V {
+            fn visit_file(&mut self, file: &mut ZipFile<'_>) -> ZipResult<()> {
+                let mut contents = String::new();
+                file.read_to_string(&mut contents)?;
+                self.0.push(contents);
+                Ok(())
+            }
+            fn visit_additional_metadata(&mut self, _: &ZipStreamFileMetadata) -> ZipResult<()> {
+                Ok(())
+            }
+        }
+
+        let mut visitor = V(Vec::new());
+        ZipStreamReader::new(io::Cursor::new(&bytes))
+            .visit_with_passwords(&mut visitor, &mut |_: &str, _: EncryptionKind| {
+                Some(Password::new(b"secret".to_vec()))
+            })
+            .unwrap();
+        assert_eq!(visitor.0, ["streamed secret", "plain"]);
+
+        let mut reader = io::Cursor::new(&bytes);
+        let mut contents = String::new();
+        super::super::read_zipfile_from_stream_decrypt(&mut reader, b"secret")
+            .unwrap()
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "streamed secret");
+
+        for result in [
+            ZipStreamReader::new(io::Cursor::new(&bytes)).visit(&mut DummyVisitor),
+            ZipStreamReader::new(io::Cursor::new(&bytes))
+                .visit_with_passwords(&mut DummyVisitor, &mut |_: &str, _: EncryptionKind| {
+                    Some(Password::new(b"wrong".to_vec()))
+                }),
+        ] {
+            assert!(matches!(result, Err(ZipError::UnsupportedArchive(_))));
+        }
+    }
+
     /// test case to ensure we don't preemptively over allocate based on the
     /// declared number of files in the CDE of an invalid zip when the number of
     /// files declared is more than the alleged offset in the CDE
@@ -369,4 +481,70 @@ mod test {
         .visit(&mut DummyVisitor)
         .unwrap_err();
     }
+
+    /// An archive with the file "aes.txt", containing "streamed with AES", encrypted with the
+    /// password "secret" in the WinZip AE-2 format with AES-256, which cannot be written yet
+    #[cfg(feature = "aes-crypto")]
+    const AES_ARCHIVE: [u8; 179] = [
+        0x50, 0x4b, 0x03, 0x04, 0x33, 0x00, 0x01, 0x00, 0x63, 0x00, 0x00, 0x00, 0x21, 0x57, 0x00,
+        0x00, 0x00, 0x00, 0x2d, 0x00, 0x00, 0x00, 0x11, 0x00, 0x00, 0x00, 0x07, 0x00, 0x0b, 0x00,
+        0x61, 0x65, 0x73, 0x2e, 0x74, 0x78, 0x74, 0x01, 0x99, 0x07, 0x00, 0x02, 0x00, 0x41, 0x45,
+        0x03, 0x00, 0x00, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a, 0x5a,
+        0x5a, 0x5a, 0x5a, 0x5a, 0x02, 0xb6, 0xd8, 0xfc, 0x27, 0xff, 0x62, 0xdc, 0xc7, 0x33, 0x92,
+        0xaa, 0x10, 0xf6, 0xd0, 0x70, 0x6c, 0x4e, 0xad, 0xcb, 0x95, 0x3e, 0x6d, 0x33, 0xa3, 0x10,
+        0xa4, 0xf2, 0xca, 0x50, 0x4b, 0x01, 0x02, 0x33, 0x03, 0x33, 0x00, 0x01, 0x00, 0x63, 0x00,
+        0x00, 0x00, 0x21, 0x57, 0x00, 0x00, 0x00, 0x00, 0x2d, 0x00, 0x00, 0x00, 0x11, 0x00, 0x00,
+        0x00, 0x07, 0x00, 0x0b, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xa4, 0x81,
+        0x00, 0x00, 0x00, 0x00, 0x61, 0x65, 0x73, 0x2e, 0x74, 0x78, 0x74, 0x01, 0x99, 0x07, 0x00,
+        0x02, 0x00, 0x41, 0x45, 0x03, 0x00, 0x00, 0x50, 0x4b, 0x05, 0x06, 0x00, 0x00, 0x00, 0x00,
+        0x01, 0x00, 0x01, 0x00, 0x40, 0x00, 0x00, 0x00, 0x5d, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    #[cfg(feature = "aes-crypto")]
+    #[test]
+    fn zip_read_streaming_aes() {
+        use crate::zip::password::Password;
+        use crate::zip::read::ZipArchive;
+        use crate::zip::types::{AesMode, AesVendorVersion};
+        use std::io::Read;
+
+        // The seekable reader checks the fixture itself
+        let mut archive = ZipArchive::new(io::Cursor::new(&AES_ARCHIVE[..])).unwrap();
+        let mut contents = String::new();
+        archive
+            .by_name_decrypt("aes.txt", b"secret")
+            .unwrap()
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "streamed with AES");
+
+        let mut reader = io::Cursor::new(&AES_ARCHIVE[..]);
+        let mut contents = String::new();
+        super::super::read_zipfile_from_stream_decrypt(&mut reader, b"secret")
+            .unwrap()
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "streamed with AES");
+
+        let mut kinds = Vec::new();
+        ZipStreamReader::new(io::Cursor::new(&AES_ARCHIVE[..]))
+            .visit_with_passwords(&mut DummyVisitor, &mut |_: &str, kind| {
+                kinds.push(kind);
+                Some(Password::new(b"secret".to_vec()))
+            })
+            .unwrap();
+        assert_eq!(
+            kinds,
+            [EncryptionKind::Aes {
+                mode: AesMode::Aes256,
+                vendor_version: AesVendorVersion::Ae2,
+            }]
+        );
+
+        let mut reader = io::Cursor::new(&AES_ARCHIVE[..]);
+        let result = super::super::read_zipfile_from_stream_decrypt(&mut reader, b"wrong");
+        assert!(matches!(result, Err(ZipError::UnsupportedArchive(_))));
+    }
 }
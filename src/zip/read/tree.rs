@@ -0,0 +1,317 @@
+use std::collections::BTreeMap;
+use std::io::{self, Read, Seek};
+
+use super::{ZipArchive, ZipError, ZipFile, ZipFileData, ZipResult};
+use crate::zip::types::{enclosed_components, DateTime};
+use crate::zip::zstd_dictionary;
+
+/// A directory tree of the files in a [`ZipArchive`], to browse it like a read-only
+/// filesystem.
+///
+/// Created by [`ZipArchive::tree`]. Paths are relative to the root of the archive, with `/`
+/// separators, and are normalised like [`ZipFile::enclosed_name`]: `.` components are
+/// ignored, `..` components go up one directory, and absolute paths are never found. Files
+/// whose name is not enclosed in the archive are left out of the tree, as well as the zstd
+/// dictionaries, see [`ZipFile::is_zstd_dictionary`].
+///
+/// Directories exist when they have an entry of their own, as well as when they only appear
+/// in the names of other files. When several files have the same normalised name, the last
+/// one is used, as with [`ZipArchive::by_name`].
+pub struct ZipTree<'a, R> {
+    archive: &'a mut ZipArchive<R>,
+    nodes: Vec<Node>,
+}
+
+struct Node {
+    name: String,
+    parent: Option<usize>,
+    /// Index of the entry of the file in the archive, if any
+    index: Option<usize>,
+    children: BTreeMap<String, usize>,
+}
+
+impl Node {
+    fn new(name: &str, parent: Option<usize>) -> Self {
+        Node {
+            name: name.to_owned(),
+            parent,
+            index: None,
+            children: BTreeMap::new(),
+        }
+    }
+}
+
+impl<'a, R: Read + Seek> ZipTree<'a, R> {
+    pub(crate) fn new(archive: &'a mut ZipArchive<R>) -> Self {
+        let mut nodes = vec![Node::new("", None)];
+        for (index, file) in archive.shared.files.iter().enumerate() {
+            if zstd_dictionary::is_dictionary(file) {
+                continue;
+            }
+            let components = match enclosed_components(&file.file_name) {
+                Some(components) => components,
+                None => continue,
+            };
+            let mut id = 0;
+            for component in components {
+                id = match nodes[id].children.get(component) {
+                    Some(&child) => child,
+                    None => {
+                        let child = nodes.len();
+                        nodes.push(Node::new(component, Some(id)));
+                        nodes[id].children.insert(component.to_owned(), child);
+                        child
+                    }
+                };
+            }
+            if id != 0 {
+                nodes[id].index = Some(index);
+            }
+        }
+        ZipTree { archive, nodes }
+    }
+
+    /// The root directory of the archive
+    pub fn root(&self) -> TreeNode<'_> {
+        self.node(0)
+    }
+
+    /// Look up a file or directory by path
+    pub fn get(&self, path: &str) -> Option<TreeNode<'_>> {
+        let mut id = 0;
+        for component in enclosed_components(path)? {
+            id = *self.nodes[id].children.get(component)?;
+        }
+        Some(self.node(id))
+    }
+
+    /// Get the metadata of a file or directory
+    pub fn metadata(&self, path: &str) -> ZipResult<NodeMetadata> {
+        Ok(self.get(path).ok_or(ZipError::FileNotFound)?.metadata())
+    }
+
+    /// List the contents of a directory, sorted by name
+    pub fn read_dir(&self, path: &str) -> ZipResult<Vec<TreeNode<'_>>> {
+        let node = self.get(path).ok_or(ZipError::FileNotFound)?;
+        if !node.is_dir() {
+            return Err(invalid_input("Not a directory"));
+        }
+        Ok(node.children().collect())
+    }
+
+    /// Open a file for reading
+    pub fn open(&mut self, path: &str) -> ZipResult<ZipFile<'_>> {
+        let node = self.get(path).ok_or(ZipError::FileNotFound)?;
+        if node.is_dir() {
+            return Err(invalid_input("Is a directory"));
+        }
+        let index = node.index().ok_or(ZipError::FileNotFound)?;
+        self.archive.by_index(index)
+    }
+
+    fn node(&self, id: usize) -> TreeNode<'_> {
+        TreeNode {
+            nodes: &self.nodes,
+            files: &self.archive.shared.files,
+            id,
+        }
+    }
+}
+
+fn invalid_input(message: &str) -> ZipError {
+    ZipError::Io(io::Error::new(io::ErrorKind::InvalidInput, message))
+}
+
+/// A file or directory in a [`ZipTree`]
+#[derive(Clone, Copy)]
+pub struct TreeNode<'t> {
+    nodes: &'t [Node],
+    files: &'t [ZipFileData],
+    id: usize,
+}
+
+impl<'t> TreeNode<'t> {
+    /// Name of the file or directory, which is empty for the root
+    pub fn name(&self) -> &'t str {
+        &self.nodes[self.id].name
+    }
+
+    /// Normalised path from the root of the archive, with `/` separators
+    pub fn path(&self) -> String {
+        let mut names = Vec::new();
+        let mut node = Some(*self);
+        while let Some(current) = node.filter(|node| node.id != 0) {
+            names.push(current.name());
+            node = current.parent();
+        }
+        names.reverse();
+        names.join("/")
+    }
+
+    /// Index of the entry of this file or directory in the archive, or `None` for
+    /// directories that only appear in the names of other files
+    pub fn index(&self) -> Option<usize> {
+        self.nodes[self.id].index
+    }
+
+    /// Returns whether this is a directory, with or without an entry of its own
+    pub fn is_dir(&self) -> bool {
+        let node = &self.nodes[self.id];
+        match node.index {
+            _ if !node.children.is_empty() => true,
+            Some(index) => {
+                let name = &self.files[index].file_name;
+                name.ends_with('/') || name.ends_with('\\')
+            }
+            None => true,
+        }
+    }
+
+    /// Returns whether this is a regular file
+    pub fn is_file(&self) -> bool {
+        !self.is_dir()
+    }
+
+    /// The directory containing this node, or `None` for the root
+    pub fn parent(&self) -> Option<TreeNode<'t>> {
+        let parent = self.nodes[self.id].parent?;
+        Some(TreeNode {
+            id: parent,
+            ..*self
+        })
+    }
+
+    /// Look up a direct child of this directory by name
+    pub fn child(&self, name: &str) -> Option<TreeNode<'t>> {
+        let id = *self.nodes[self.id].children.get(name)?;
+        Some(TreeNode { id, ..*self })
+    }
+
+    /// The contents of this directory, sorted by name
+    pub fn children(&self) -> impl Iterator<Item = TreeNode<'t>> {
+        let node = *self;
+        self.nodes[self.id]
+            .children
+            .values()
+            .map(move |&id| TreeNode { id, ..node })
+    }
+
+    /// The metadata of this file or directory
+    pub fn metadata(&self) -> NodeMetadata {
+        let index = self.index();
+        let file = index.map(|index| &self.files[index]);
+        NodeMetadata {
+            is_dir: self.is_dir(),
+            index,
+            size: file.map_or(0, |file| file.uncompressed_size),
+            compressed_size: file.map_or(0, |file| file.compressed_size),
+            last_modified: file.map(|file| file.last_modified_time),
+            unix_mode: file.and_then(|file| file.unix_mode()),
+            encrypted: file.filter(|file| file.encrypted).is_some(),
+        }
+    }
+}
+
+impl std::fmt::Debug for TreeNode<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TreeNode")
+            .field("path", &self.path())
+            .field("index", &self.index())
+            .finish()
+    }
+}
+
+/// Metadata of a file or directory in a [`ZipTree`]
+///
+/// Directories without an entry of their own have no size, modification time or mode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeMetadata {
+    /// Whether this is a directory
+    pub is_dir: bool,
+    /// Index of the entry in the archive, if any
+    pub index: Option<usize>,
+    /// Size of the file when extracted
+    pub size: u64,
+    /// Size of the file in the archive
+    pub compressed_size: u64,
+    /// Last modified time, with a 2 second precision
+    pub last_modified: Option<DateTime>,
+    /// Unix mode of the file, if known
+    pub unix_mode: Option<u32>,
+    /// Whether the file is encrypted
+    pub encrypted: bool,
+}
+
+#[cfg(test)]
+mod test {
+    use crate::zip::read::ZipArchive;
+    use crate::zip::result::ZipError;
+    use crate::zip::write::{FileOptions, ZipWriter};
+    use std::io::{self, Read, Write};
+
+    fn archive() -> ZipArchive<io::Cursor<Vec<u8>>> {
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        writer.add_directory("dir", FileOptions::default()).unwrap();
+        for name in &[
+            "dir/a.txt",
+            "implicit/sub/b.txt",
+            "./other/../top.txt",
+            "../outside.txt",
+        ] {
+            writer.start_file(*name, FileOptions::default()).unwrap();
+            writer.write_all(name.as_bytes()).unwrap();
+        }
+        ZipArchive::new(writer.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn tree_navigation() {
+        let mut archive = archive();
+        let tree = archive.tree();
+
+        let names: Vec<_> = tree
+            .read_dir("")
+            .unwrap()
+            .iter()
+            .map(|n| n.name())
+            .collect();
+        assert_eq!(names, ["dir", "implicit", "top.txt"]);
+        assert!(tree.get("../outside.txt").is_none());
+        assert!(tree.get("/top.txt").is_none());
+
+        let file = tree.get("implicit/./sub//b.txt").unwrap();
+        assert_eq!(file.path(), "implicit/sub/b.txt");
+        assert!(file.is_file());
+        let implicit = file.parent().unwrap().parent().unwrap();
+        assert_eq!(implicit.path(), "implicit");
+        assert_eq!(implicit.index(), None);
+        assert!(implicit.metadata().is_dir);
+        assert_eq!(implicit.metadata().last_modified, None);
+
+        let dir = tree.metadata("dir").unwrap();
+        assert!(dir.is_dir);
+        assert_eq!(dir.index, Some(0));
+        assert_eq!(tree.metadata("dir/a.txt").unwrap().size, 9);
+        assert!(matches!(
+            tree.metadata("missing"),
+            Err(ZipError::FileNotFound)
+        ));
+        assert!(tree.read_dir("top.txt").is_err());
+    }
+
+    #[test]
+    fn tree_open() {
+        let mut archive = archive();
+        let mut tree = archive.tree();
+
+        let mut contents = String::new();
+        tree.open("other/../top.txt")
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "./other/../top.txt");
+        assert!(tree.open("dir").is_err());
+        assert!(tree.open("implicit").is_err());
+        assert!(matches!(tree.open("nope"), Err(ZipError::FileNotFound)));
+    }
+}
@@ -19,8 +19,48 @@ impl fmt::Display for InvalidPassword {
 
 impl Error for InvalidPassword {}
 
+/// How a new file breaks the [`crate::zip::write::EncryptionPolicy`] of a writer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PolicyViolation {
+    /// The file is encrypted with ZipCrypto, which the policy forbids
+    ZipCryptoForbidden,
+    /// The password of the file is shorter than the policy allows
+    PasswordTooShort {
+        /// Length of the password, in bytes
+        length: usize,
+        /// Minimum length allowed by the policy, in bytes
+        min_length: usize,
+    },
+}
+
+impl fmt::Display for PolicyViolation {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PolicyViolation::ZipCryptoForbidden => {
+                write!(
+                    fmt,
+                    "ZipCrypto encryption is forbidden by the encryption policy"
+                )
+            }
+            PolicyViolation::PasswordTooShort { min_length, .. } => write!(
+                fmt,
+                "Password is shorter than the {min_length} bytes the encryption policy requires"
+            ),
+        }
+    }
+}
+
+impl Error for PolicyViolation {}
+
 /// Error type for Zip
+///
+/// Errors about a specific entry or location in the archive are wrapped in
+/// [`ZipError::Context`], including errors that used to be returned as they are, so matches on
+/// the other variants should be done on [`ZipError::without_context`]. New variants may be
+/// added, so matches on this type need a wildcard arm.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum ZipError {
     /// An Error caused by I/O
     Io(io::Error),
@@ -33,6 +73,56 @@ pub enum ZipError {
 
     /// The requested file could not be found in the archive
     FileNotFound,
+
+    /// The operation was cancelled by a [`crate::zip::progress::Progress`] callback
+    Cancelled,
+
+    /// A new file is not allowed by the [`crate::zip::write::EncryptionPolicy`] of the writer
+    PolicyViolation(PolicyViolation),
+
+    /// An error concerning a specific entry or location in the archive
+    ///
+    /// Use [`ZipError::without_context`] to match on the underlying error.
+    Context(Box<ErrorContext>),
+}
+
+/// Where an error happened in an archive, see [`ZipError::Context`]
+#[derive(Debug)]
+pub struct ErrorContext {
+    /// The underlying error, which never has a context itself
+    pub error: ZipError,
+    /// Name of the entry concerned, if known
+    pub entry_name: Option<String>,
+    /// Index of the entry concerned in the central directory, if known
+    pub entry_index: Option<usize>,
+    /// Offset in the archive of the structure that could not be read
+    pub offset: Option<u64>,
+    /// Name of the field or structure that could not be read
+    pub field: Option<&'static str>,
+}
+
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}", self.error)?;
+
+        let mut details = Vec::new();
+        match (self.field, self.offset) {
+            (Some(field), Some(offset)) => details.push(format!("{field} at {offset:#x}")),
+            (Some(field), None) => details.push(field.to_owned()),
+            (None, Some(offset)) => details.push(format!("at {offset:#x}")),
+            (None, None) => {}
+        }
+        match (self.entry_index, &self.entry_name) {
+            (Some(index), Some(name)) => details.push(format!("for entry {index} {name:?}")),
+            (Some(index), None) => details.push(format!("for entry {index}")),
+            (None, Some(name)) => details.push(format!("for entry {name:?}")),
+            (None, None) => {}
+        }
+        if !details.is_empty() {
+            write!(fmt, " ({})", details.join(", "))?;
+        }
+        Ok(())
+    }
 }
 
 impl From<io::Error> for ZipError {
@@ -48,6 +138,9 @@ impl fmt::Display for ZipError {
             ZipError::InvalidArchive(err) => write!(fmt, "invalid Zip archive: {err}"),
             ZipError::UnsupportedArchive(err) => write!(fmt, "unsupported Zip archive: {err}"),
             ZipError::FileNotFound => write!(fmt, "specified file not found in archive"),
+            ZipError::Cancelled => write!(fmt, "operation cancelled"),
+            ZipError::PolicyViolation(violation) => write!(fmt, "{violation}"),
+            ZipError::Context(context) => write!(fmt, "{context}"),
         }
     }
 }
@@ -56,6 +149,7 @@ impl Error for ZipError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
         match self {
             ZipError::Io(err) => Some(err),
+            ZipError::Context(context) => context.error.source(),
             _ => None,
         }
     }
@@ -67,18 +161,89 @@ impl ZipError {
     /// ```rust,no_run
     /// # use zip::result::ZipError;
     /// # let mut archive = zip::ZipArchive::new(std::io::Cursor::new(&[])).unwrap();
-    /// match archive.by_index(1) {
-    ///     Err(ZipError::UnsupportedArchive(ZipError::PASSWORD_REQUIRED)) => eprintln!("a password is needed to unzip this file"),
-    ///     _ => (),
+    /// if let Err(e) = archive.by_index(1) {
+    ///     if let ZipError::UnsupportedArchive(ZipError::PASSWORD_REQUIRED) = e.without_context() {
+    ///         eprintln!("a password is needed to unzip this file");
+    ///     }
     /// }
     /// # ()
     /// ```
     pub const PASSWORD_REQUIRED: &'static str = "Password required to decrypt file";
+
+    /// Where the error happened, if known
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            ZipError::Context(context) => Some(context),
+            _ => None,
+        }
+    }
+
+    /// The error without its context
+    ///
+    /// ```rust,no_run
+    /// # use zip::result::ZipError;
+    /// # let mut archive = zip::ZipArchive::new(std::io::Cursor::new(&[])).unwrap();
+    /// if let Err(e) = archive.by_index(1) {
+    ///     if let ZipError::InvalidArchive(message) = e.without_context() {
+    ///         eprintln!("corrupt archive: {message}");
+    ///     }
+    /// }
+    /// ```
+    pub fn without_context(&self) -> &ZipError {
+        match self {
+            ZipError::Context(context) => &context.error,
+            error => error,
+        }
+    }
+
+    fn into_context(self) -> Box<ErrorContext> {
+        match self {
+            ZipError::Context(context) => context,
+            error => Box::new(ErrorContext {
+                error,
+                entry_name: None,
+                entry_index: None,
+                offset: None,
+                field: None,
+            }),
+        }
+    }
+
+    /// Add the location of the structure that could not be read, unless it is already known.
+    pub(crate) fn with_offset(self, offset: u64, field: &'static str) -> ZipError {
+        let mut context = self.into_context();
+        if context.field.is_none() {
+            context.offset = Some(offset);
+            context.field = Some(field);
+        }
+        ZipError::Context(context)
+    }
+
+    /// Add the name of the entry concerned, unless it is already known.
+    pub(crate) fn with_entry_name(self, name: &str) -> ZipError {
+        let mut context = self.into_context();
+        if context.entry_name.is_none() {
+            context.entry_name = Some(name.to_owned());
+        }
+        ZipError::Context(context)
+    }
+
+    /// Add the index of the entry concerned, unless it is already known.
+    pub(crate) fn with_entry_index(self, index: usize) -> ZipError {
+        let mut context = self.into_context();
+        context.entry_index.get_or_insert(index);
+        ZipError::Context(context)
+    }
 }
 
 impl From<ZipError> for io::Error {
     fn from(err: ZipError) -> io::Error {
-        io::Error::new(io::ErrorKind::Other, err)
+        let kind = match err.without_context() {
+            ZipError::Io(err) => err.kind(),
+            ZipError::PolicyViolation(_) => io::ErrorKind::InvalidInput,
+            _ => io::ErrorKind::Other,
+        };
+        io::Error::new(kind, err)
     }
 }
 
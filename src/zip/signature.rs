@@ -0,0 +1,212 @@
+//! Ed25519 signatures of whole archives
+//!
+//! The signature is stored in a trailer at the end of the archive comment: an 8 byte magic
+//! number, the id of the signing key and the signature itself, both in hexadecimal. It signs
+//! the SHA-512 digest of everything before the trailer, that is the file data, the central
+//! directory, the end of central directory record and the rest of the comment.
+//!
+//! The trailer is ASCII text, so that it cannot contain the signature of the end of central
+//! directory record, which readers search backwards from the end of the file. Other tools
+//! show it at the end of the archive comment.
+
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use sha2::{Digest, Sha512};
+
+use crate::zip::result::{ZipError, ZipResult};
+
+/// Size of the trailer at the end of the comment of signed archives: the magic number, the
+/// key id and the signature, both hex-encoded
+pub(crate) const TRAILER_SIZE: usize = 8 + 2 * 8 + 2 * 64;
+
+const MAGIC: &[u8; 8] = b"ZIPSIGv1";
+
+/// Id of an Ed25519 public key: the first 8 bytes of its SHA-512 digest
+pub(crate) fn key_id(public_key: &[u8; 32]) -> [u8; 8] {
+    let mut id = [0; 8];
+    id.copy_from_slice(&Sha512::digest(public_key)[..8]);
+    id
+}
+
+/// The id of the key that signed an archive with this comment, if it is signed
+pub(crate) fn signer(comment: &[u8]) -> Option<[u8; 8]> {
+    let trailer = comment
+        .len()
+        .checked_sub(TRAILER_SIZE)
+        .map(|start| &comment[start..])?;
+    if &trailer[..8] != MAGIC {
+        return None;
+    }
+    let mut id = [0; 8];
+    decode_hex(&trailer[8..24], &mut id)?;
+    Some(id)
+}
+
+fn encode_hex(bytes: &[u8]) -> Vec<u8> {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+    bytes
+        .iter()
+        .flat_map(|&byte| [DIGITS[(byte >> 4) as usize], DIGITS[(byte & 0xf) as usize]])
+        .collect()
+}
+
+/// Decode the lowercase hexadecimal `hex` into `bytes`, which must be half as long
+fn decode_hex(hex: &[u8], bytes: &mut [u8]) -> Option<()> {
+    let digit = |c: u8| match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        _ => None,
+    };
+    for (byte, pair) in bytes.iter_mut().zip(hex.chunks_exact(2)) {
+        *byte = digit(pair[0])? << 4 | digit(pair[1])?;
+    }
+    Some(())
+}
+
+fn digest<R: Read + Seek>(reader: &mut R, len: u64) -> io::Result<[u8; 64]> {
+    reader.seek(SeekFrom::Start(0))?;
+    let mut hasher = Sha512::new();
+    let copied = io::copy(&mut reader.take(len), &mut hasher)?;
+    if copied != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(hasher.finalize().into())
+}
+
+/// Sign an archive whose comment ends with `TRAILER_SIZE` placeholder bytes, writing the
+/// trailer over them
+pub(crate) fn sign<W: Read + Write + Seek>(writer: &mut W, secret_key: &[u8; 32]) -> ZipResult<()> {
+    let key = SigningKey::from_bytes(secret_key);
+    let signed_len = writer.seek(SeekFrom::End(0))? - TRAILER_SIZE as u64;
+    let signature = key.sign(&digest(writer, signed_len)?);
+
+    writer.seek(SeekFrom::Start(signed_len))?;
+    writer.write_all(MAGIC)?;
+    writer.write_all(&encode_hex(&key_id(key.verifying_key().as_bytes())))?;
+    writer.write_all(&encode_hex(&signature.to_bytes()))?;
+    Ok(())
+}
+
+/// Check the signature of an archive with the given comment, read by `reader`
+pub(crate) fn verify<R: Read + Seek>(
+    reader: &mut R,
+    comment: &[u8],
+    public_key: &[u8; 32],
+) -> ZipResult<()> {
+    let signer = signer(comment).ok_or(ZipError::InvalidArchive("Archive is not signed"))?;
+    if signer != key_id(public_key) {
+        return Err(ZipError::InvalidArchive(
+            "Archive is signed with another key",
+        ));
+    }
+    let key = VerifyingKey::from_bytes(public_key).map_err(|_| {
+        ZipError::Io(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Invalid Ed25519 public key",
+        ))
+    })?;
+
+    // The trailer has to end the file, so that the signature covers everything before it
+    let end = reader.seek(SeekFrom::End(0))?;
+    let signed_len = end
+        .checked_sub(TRAILER_SIZE as u64)
+        .ok_or(ZipError::InvalidArchive("Archive is not signed"))?;
+    let mut trailer = [0; TRAILER_SIZE];
+    reader.seek(SeekFrom::Start(signed_len))?;
+    reader.read_exact(&mut trailer)?;
+    if trailer[..] != comment[comment.len() - TRAILER_SIZE..] {
+        return Err(ZipError::InvalidArchive(
+            "Archive signature is not at the end of the file",
+        ));
+    }
+
+    let mut signature = [0; 64];
+    decode_hex(&trailer[24..], &mut signature)
+        .ok_or(ZipError::InvalidArchive("Invalid archive signature"))?;
+    key.verify_strict(
+        &digest(reader, signed_len)?,
+        &Signature::from_bytes(&signature),
+    )
+    .map_err(|_| ZipError::InvalidArchive("Invalid archive signature"))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::zip::compression::CompressionMethod;
+    use crate::zip::read::ZipArchive;
+    use crate::zip::result::ZipError;
+    use crate::zip::write::{FileOptions, ZipWriter};
+    use ed25519_dalek::SigningKey;
+    use std::io::{self, Write};
+
+    const SECRET_KEY: [u8; 32] = [7; 32];
+
+    fn public_key(secret_key: &[u8; 32]) -> [u8; 32] {
+        SigningKey::from_bytes(secret_key)
+            .verifying_key()
+            .to_bytes()
+    }
+
+    fn signed_archive() -> Vec<u8> {
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        writer.set_comment("release 1.0");
+        let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+        writer.start_file("data.txt", options).unwrap();
+        writer.write_all(b"signed contents").unwrap();
+        writer.finish_signed(&SECRET_KEY).unwrap().into_inner()
+    }
+
+    fn verify(bytes: Vec<u8>, public_key: &[u8; 32]) -> Result<(), ZipError> {
+        let mut archive = ZipArchive::new(io::Cursor::new(bytes))?;
+        archive.verify_signature(public_key)
+    }
+
+    #[test]
+    fn signature_roundtrip() {
+        let bytes = signed_archive();
+        let archive = ZipArchive::new(io::Cursor::new(bytes.clone())).unwrap();
+        assert!(archive.comment().starts_with(b"release 1.0"));
+        let trailer = &archive.comment()[11..];
+        assert_eq!(trailer.len(), super::TRAILER_SIZE);
+        assert!(trailer.starts_with(b"ZIPSIGv1"));
+        assert!(trailer.iter().all(u8::is_ascii_alphanumeric));
+        assert_eq!(
+            archive.signature_key_id(),
+            Some(super::key_id(&public_key(&SECRET_KEY)))
+        );
+        verify(bytes, &public_key(&SECRET_KEY)).unwrap();
+    }
+
+    #[test]
+    fn signature_rejected() {
+        let bytes = signed_archive();
+        assert!(matches!(
+            verify(bytes.clone(), &public_key(&[8; 32])),
+            Err(ZipError::InvalidArchive(
+                "Archive is signed with another key"
+            ))
+        ));
+
+        let position = bytes
+            .windows(15)
+            .position(|window| window == b"signed contents")
+            .unwrap();
+        let mut tampered = bytes.clone();
+        tampered[position] = b'S';
+        assert!(matches!(
+            verify(tampered, &public_key(&SECRET_KEY)),
+            Err(ZipError::InvalidArchive("Invalid archive signature"))
+        ));
+
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        writer
+            .start_file("data.txt", FileOptions::default())
+            .unwrap();
+        let unsigned = writer.finish().unwrap().into_inner();
+        assert!(matches!(
+            verify(unsigned, &public_key(&SECRET_KEY)),
+            Err(ZipError::InvalidArchive("Archive is not signed"))
+        ));
+    }
+}
@@ -51,6 +51,9 @@ mod atomic {
     }
 }
 
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
+
 #[cfg(feature = "time")]
 use crate::zip::result::DateTimeRangeError;
 #[cfg(feature = "time")]
@@ -75,6 +78,51 @@ impl System {
     }
 }
 
+/// MS-DOS and Windows attributes of a file, stored in the low byte of its external attributes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct DosAttributes {
+    /// The file cannot be modified
+    pub read_only: bool,
+    /// The file is not listed by default
+    pub hidden: bool,
+    /// The file is used by the operating system
+    pub system: bool,
+    /// The file is a directory
+    pub directory: bool,
+    /// The file has changed since it was last backed up
+    pub archive: bool,
+}
+
+impl DosAttributes {
+    const READ_ONLY: u8 = 0x01;
+    const HIDDEN: u8 = 0x02;
+    const SYSTEM: u8 = 0x04;
+    const DIRECTORY: u8 = 0x10;
+    const ARCHIVE: u8 = 0x20;
+
+    /// Decode the attributes from their MS-DOS representation
+    pub fn from_bits(bits: u8) -> DosAttributes {
+        DosAttributes {
+            read_only: bits & Self::READ_ONLY != 0,
+            hidden: bits & Self::HIDDEN != 0,
+            system: bits & Self::SYSTEM != 0,
+            directory: bits & Self::DIRECTORY != 0,
+            archive: bits & Self::ARCHIVE != 0,
+        }
+    }
+
+    /// Encode the attributes in their MS-DOS representation
+    pub fn bits(&self) -> u8 {
+        let flag = |set: bool, bit: u8| if set { bit } else { 0 };
+        flag(self.read_only, Self::READ_ONLY)
+            | flag(self.hidden, Self::HIDDEN)
+            | flag(self.system, Self::SYSTEM)
+            | flag(self.directory, Self::DIRECTORY)
+            | flag(self.archive, Self::ARCHIVE)
+    }
+}
+
 /// Representation of a moment in time.
 ///
 /// Zip files use an old format from DOS to store timestamps,
@@ -92,7 +140,12 @@ impl System {
 ///
 /// Modern zip files store more precise timestamps, which are ignored by [`crate::read::ZipArchive`],
 /// so keep in mind that these timestamps are unreliable. [We're working on this](https://github.com/zip-rs/zip/issues/156#issuecomment-652981904).
-#[derive(Debug, Clone, Copy)]
+///
+/// Timestamps are ordered chronologically. When deserialized, they are checked against the
+/// bounds of [`DateTime::from_date_and_time`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", serde(try_from = "DateTimeFields"))]
 pub struct DateTime {
     year: u16,
     month: u8,
@@ -102,6 +155,35 @@ pub struct DateTime {
     second: u8,
 }
 
+/// The fields of a serialized [`DateTime`], before they are checked
+#[cfg(feature = "serialize")]
+#[derive(Deserialize)]
+struct DateTimeFields {
+    year: u16,
+    month: u8,
+    day: u8,
+    hour: u8,
+    minute: u8,
+    second: u8,
+}
+
+#[cfg(feature = "serialize")]
+impl TryFrom<DateTimeFields> for DateTime {
+    type Error = &'static str;
+
+    fn try_from(fields: DateTimeFields) -> Result<Self, Self::Error> {
+        DateTime::from_date_and_time(
+            fields.year,
+            fields.month,
+            fields.day,
+            fields.hour,
+            fields.minute,
+            fields.second,
+        )
+        .map_err(|()| "date and time out of bounds")
+    }
+}
+
 impl ::std::default::Default for DateTime {
     /// Constructs an 'default' datetime of 1980-01-01 00:00:00
     fn default() -> DateTime {
@@ -354,6 +436,26 @@ pub struct ZipFileData {
     pub aes_mode: Option<(AesMode, AesVendorVersion)>,
 }
 
+/// Split a file name into its normal components, resolving `.` and `..`, or `None` if it is
+/// absolute, contains a NUL byte or leads outside of the archive
+pub(crate) fn enclosed_components(name: &str) -> Option<Vec<&str>> {
+    if name.contains('\0') {
+        return None;
+    }
+    let mut components = Vec::new();
+    for component in path::Path::new(name).components() {
+        match component {
+            path::Component::Prefix(_) | path::Component::RootDir => return None,
+            path::Component::ParentDir => {
+                components.pop()?;
+            }
+            path::Component::Normal(component) => components.push(component.to_str()?),
+            path::Component::CurDir => (),
+        }
+    }
+    Some(components)
+}
+
 impl ZipFileData {
     pub fn file_name_sanitized(&self) -> ::std::path::PathBuf {
         let no_null_filename = match self.file_name.find('\0') {
@@ -383,20 +485,29 @@ impl ZipFileData {
     }
 
     pub(crate) fn enclosed_name(&self) -> Option<&path::Path> {
-        if self.file_name.contains('\0') {
+        enclosed_components(&self.file_name)?;
+        Some(path::Path::new(&self.file_name))
+    }
+
+    /// Get the MS-DOS attributes of the file, which are always set by MS-DOS and Windows
+    /// archivers, and sometimes by Unix ones
+    pub(crate) fn dos_attributes(&self) -> Option<DosAttributes> {
+        let bits = self.external_attributes as u8;
+        if self.external_attributes == 0 || (self.system != System::Dos && bits == 0) {
             return None;
         }
-        let path = path::Path::new(&self.file_name);
-        let mut depth = 0usize;
-        for component in path.components() {
-            match component {
-                path::Component::Prefix(_) | path::Component::RootDir => return None,
-                path::Component::ParentDir => depth = depth.checked_sub(1)?,
-                path::Component::Normal(_) => depth += 1,
-                path::Component::CurDir => (),
+        Some(DosAttributes::from_bits(bits))
+    }
+
+    /// Get the unix mode to extract the file with, which is not writable for read-only files
+    pub(crate) fn extracted_unix_mode(&self) -> Option<u32> {
+        let mode = self.unix_mode()?;
+        match self.dos_attributes() {
+            Some(attributes) if attributes.read_only && mode & 0o170000 != ffi::S_IFDIR => {
+                Some(mode & !0o222)
             }
+            _ => Some(mode),
         }
-        Some(path)
     }
 
     /// Get unix mode for the file
@@ -445,14 +556,16 @@ impl ZipFileData {
 ///
 /// According to the [specification](https://www.winzip.com/win/en/aes_info.html#winzip11) AE-2
 /// does not make use of the CRC check.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub enum AesVendorVersion {
     Ae1,
     Ae2,
 }
 
 /// AES variant used.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub enum AesMode {
     Aes128,
     Aes192,
@@ -485,6 +598,23 @@ mod test {
         assert_eq!(System::from_u8(3), System::Unix);
     }
 
+    #[cfg(feature = "serialize")]
+    #[test]
+    fn datetime_deserialize_checks_bounds() {
+        use super::DateTime;
+
+        let datetime = DateTime::from_date_and_time(2023, 6, 30, 23, 59, 58).unwrap();
+        let serialized = serde_json::to_string(&datetime).unwrap();
+        assert_eq!(
+            serde_json::from_str::<DateTime>(&serialized).unwrap(),
+            datetime
+        );
+
+        let invalid = serialized.replace("\"month\":6", "\"month\":13");
+        assert_ne!(invalid, serialized);
+        assert!(serde_json::from_str::<DateTime>(&invalid).is_err());
+    }
+
     #[test]
     fn sanitize() {
         use super::*;
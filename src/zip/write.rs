@@ -1,17 +1,29 @@
 //! Types for creating ZIP archives
 
 use crate::zip::compression::CompressionMethod;
+use crate::zip::encoding::Encoding;
+use crate::zip::progress::{self, Progress};
 use crate::zip::read::{central_header_to_zip_file, ZipArchive, ZipFile};
-use crate::zip::result::{ZipError, ZipResult};
+use crate::zip::result::{PolicyViolation, ZipError, ZipResult};
+#[cfg(feature = "signing")]
+use crate::zip::signature;
 use crate::zip::spec;
-use crate::zip::types::{AtomicU64, DateTime, System, ZipFileData, DEFAULT_VERSION};
+use crate::zip::types::{AtomicU64, DateTime, DosAttributes, System, ZipFileData, DEFAULT_VERSION};
+use crate::zip::zstd_dictionary;
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use crc32fast::Hasher;
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
 use std::convert::TryInto;
 use std::default::Default;
+use std::fs;
 use std::io;
 use std::io::prelude::*;
 use std::mem;
+use std::path::Path;
+use zeroize::Zeroizing;
 
 #[cfg(any(
     feature = "deflate",
@@ -101,6 +113,16 @@ pub(crate) mod zip_writer {
         pub(super) writing_to_central_extra_field_only: bool,
         pub(super) writing_raw: bool,
         pub(super) comment: Vec<u8>,
+        pub(super) force_zip64: bool,
+        pub(super) deduplicate: bool,
+        pub(super) blobs: HashMap<super::BlobKey, usize>,
+        pub(super) duplicates: Vec<super::Duplicate>,
+        pub(super) stream_end: u64,
+        /// The id and contents of the dictionary used for `Zstd` files
+        #[cfg(feature = "zstd")]
+        pub(super) zstd_dictionary: Option<(u32, Vec<u8>)>,
+        pub(super) name_encoding: Encoding,
+        pub(super) encryption_policy: super::EncryptionPolicy,
     }
 }
 pub use zip_writer::ZipWriter;
@@ -108,25 +130,91 @@ pub use zip_writer::ZipWriter;
 #[derive(Default)]
 struct ZipWriterStats {
     hasher: Hasher,
+    /// Hash of the uncompressed data, only computed when deduplicating
+    content_hasher: Option<Sha256>,
     start: u64,
     bytes_written: u64,
 }
 
+/// Identifies the contents of a file for deduplication
+#[derive(PartialEq, Eq, Hash)]
+struct BlobKey {
+    /// SHA-256 of the uncompressed data
+    hash: [u8; 32],
+    crc32: u32,
+    size: u64,
+    compression_method: CompressionMethod,
+    zstd_dictionary: Option<u32>,
+}
+
+/// A file whose contents were already written to the archive under another name
+///
+/// See [`ZipWriter::set_deduplicate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Duplicate {
+    /// Name of the duplicate file
+    pub name: String,
+    /// Name of the first file with the same contents
+    pub original: String,
+    /// Whether the duplicate file shares the data of the original file, or was written again
+    pub deduplicated: bool,
+}
+
+/// Restrictions on the encryption of the files written by a [`ZipWriter`]
+///
+/// See [`ZipWriter::set_encryption_policy`]. The default policy allows any encryption and
+/// any password, including an empty one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EncryptionPolicy {
+    /// Reject files encrypted with the legacy ZipCrypto algorithm
+    ///
+    /// This writer cannot encrypt with AES, so this rejects all encrypted files.
+    pub forbid_zip_crypto: bool,
+    /// Reject encrypted files whose password is shorter than this many bytes
+    pub min_password_length: usize,
+}
+
+impl EncryptionPolicy {
+    /// Check the encryption of a new file with `password_length` bytes of password
+    fn check(&self, password_length: usize) -> ZipResult<()> {
+        let violation = if self.forbid_zip_crypto {
+            PolicyViolation::ZipCryptoForbidden
+        } else if password_length < self.min_password_length {
+            PolicyViolation::PasswordTooShort {
+                length: password_length,
+                min_length: self.min_password_length,
+            }
+        } else {
+            return Ok(());
+        };
+        Err(ZipError::PolicyViolation(violation))
+    }
+}
+
 struct ZipRawValues {
     crc32: u32,
     compressed_size: u64,
     uncompressed_size: u64,
+    /// Whether the raw data is encrypted with ZipCrypto, including its encryption header
+    encrypted: bool,
 }
 
 /// Metadata for a file to be written
-#[derive(Copy, Clone)]
+///
+/// The options are not `Copy`, since they may hold encryption keys, which are wiped from memory
+/// when the options are dropped.
+#[derive(Clone)]
 pub struct FileOptions {
     compression_method: CompressionMethod,
     compression_level: Option<i32>,
     last_modified_time: DateTime,
     permissions: Option<u32>,
+    dos_attributes: Option<DosAttributes>,
     large_file: bool,
-    encrypt_with: Option<crate::zip::zipcrypto::ZipCryptoKeys>,
+    encrypt_with: Option<Zeroizing<crate::zip::zipcrypto::ZipCryptoKeys>>,
+    /// Length of the password the keys of `encrypt_with` were derived from
+    password_length: usize,
+    comment: Option<String>,
 }
 
 impl FileOptions {
@@ -180,6 +268,17 @@ impl FileOptions {
         self
     }
 
+    /// Set the MS-DOS and Windows attributes of the new file.
+    ///
+    /// They are stored along with the unix permissions, which lose their write permissions
+    /// when the file is read-only. The directory attribute is set by
+    /// [`ZipWriter::add_directory`]. By default, no attributes are stored.
+    #[must_use]
+    pub fn dos_attributes(mut self, attributes: DosAttributes) -> FileOptions {
+        self.dos_attributes = Some(attributes);
+        self
+    }
+
     /// Set whether the new file's compressed and uncompressed size is less than 4 GiB.
     ///
     /// If set to `false` and the file exceeds the limit, an I/O error is thrown. If set to `true`,
@@ -190,8 +289,29 @@ impl FileOptions {
         self.large_file = large;
         self
     }
+    /// Encrypt the file with the legacy ZipCrypto algorithm
+    ///
+    /// Only the keys derived from `password` are kept, not the password itself. The copy of the
+    /// keys used by the [`ZipWriter`] is wiped from memory once the file is written.
+    ///
+    /// Starting the file fails with [`ZipError::PolicyViolation`] if ZipCrypto or the password
+    /// is not allowed by the [`EncryptionPolicy`] of the writer.
+    #[must_use]
     pub fn with_deprecated_encryption(mut self, password: &[u8]) -> FileOptions {
-        self.encrypt_with = Some(crate::zip::zipcrypto::ZipCryptoKeys::derive(password));
+        self.encrypt_with = Some(Zeroizing::new(
+            crate::zip::zipcrypto::ZipCryptoKeys::derive(password),
+        ));
+        self.password_length = password.len();
+        self
+    }
+
+    /// Set the comment of the new file
+    ///
+    /// The comment is stored in the central directory. Like file names, comments that are not
+    /// ASCII are marked as UTF-8. The default is no comment.
+    #[must_use]
+    pub fn comment<S: Into<String>>(mut self, comment: S) -> FileOptions {
+        self.comment = Some(comment.into());
         self
     }
 }
@@ -218,8 +338,11 @@ impl Default for FileOptions {
             #[cfg(not(feature = "time"))]
             last_modified_time: DateTime::default(),
             permissions: None,
+            dos_attributes: None,
             large_file: false,
             encrypt_with: None,
+            password_length: 0,
+            comment: None,
         }
     }
 }
@@ -274,6 +397,9 @@ impl<W: Write + io::Seek> Write for ZipWriter<W> {
 impl ZipWriterStats {
     fn update(&mut self, buf: &[u8]) {
         self.hasher.update(buf);
+        if let Some(content_hasher) = &mut self.content_hasher {
+            content_hasher.update(buf);
+        }
         self.bytes_written += buf.len() as u64;
     }
 }
@@ -302,7 +428,10 @@ impl<A: Read + Write + io::Seek> ZipWriter<A> {
         }
 
         let files = (0..number_of_files)
-            .map(|_| central_header_to_zip_file(&mut readwriter, archive_offset))
+            .map(|index| {
+                central_header_to_zip_file(&mut readwriter, archive_offset, Encoding::Cp437)
+                    .map_err(|e| e.with_entry_index(index))
+            })
             .collect::<Result<Vec<_>, _>>()?;
 
         let _ = readwriter.seek(io::SeekFrom::Start(directory_start)); // seek directory_start to overwrite it
@@ -316,8 +445,39 @@ impl<A: Read + Write + io::Seek> ZipWriter<A> {
             writing_to_central_extra_field_only: false,
             comment: footer.zip_file_comment,
             writing_raw: true, // avoid recomputing the last file's header
+            force_zip64: false,
+            deduplicate: false,
+            blobs: HashMap::new(),
+            duplicates: Vec::new(),
+            stream_end: 0,
+            #[cfg(feature = "zstd")]
+            zstd_dictionary: None,
+            name_encoding: Encoding::Utf8,
+            encryption_policy: EncryptionPolicy::default(),
         })
     }
+
+    /// Finish the archive like [`ZipWriter::finish`], and sign it with an Ed25519 key.
+    ///
+    /// The signature covers all the bytes written to the archive, including its comment, and
+    /// is stored in hexadecimal with the id of the key in the last 152 bytes of the comment,
+    /// after the `ZIPSIGv1` magic number. It can be checked with
+    /// [`ZipArchive::verify_signature`]. Other tools show this text at the end of the archive
+    /// comment, and nothing must be written to the archive afterwards.
+    #[cfg(feature = "signing")]
+    pub fn finish_signed(&mut self, secret_key: &[u8; 32]) -> ZipResult<A> {
+        if self.comment.len() + signature::TRAILER_SIZE > spec::ZIP64_ENTRY_THR {
+            return Err(ZipError::Io(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Archive comment is too long to be signed",
+            )));
+        }
+        self.comment
+            .resize(self.comment.len() + signature::TRAILER_SIZE, 0);
+        let mut inner = self.finish()?;
+        signature::sign(&mut inner, secret_key)?;
+        Ok(inner)
+    }
 }
 
 impl<W: Write + io::Seek> ZipWriter<W> {
@@ -334,9 +494,30 @@ impl<W: Write + io::Seek> ZipWriter<W> {
             writing_to_central_extra_field_only: false,
             writing_raw: false,
             comment: Vec::new(),
+            force_zip64: false,
+            deduplicate: false,
+            blobs: HashMap::new(),
+            duplicates: Vec::new(),
+            stream_end: 0,
+            #[cfg(feature = "zstd")]
+            zstd_dictionary: None,
+            name_encoding: Encoding::Utf8,
+            encryption_policy: EncryptionPolicy::default(),
         }
     }
 
+    /// Initializes an archive that starts with `prefix`, such as a self-extracting stub or a
+    /// shell script.
+    ///
+    /// The prefix is written first, and the offsets stored in the archive are counted from the
+    /// start of `inner`, including the prefix. This way, the archive can be read both by tools
+    /// that expect absolute offsets and by those that detect prepended data, and
+    /// [`ZipArchive::offset`] reports zero when reading it back.
+    pub fn new_with_prefix(mut inner: W, prefix: &[u8]) -> ZipResult<ZipWriter<W>> {
+        inner.write_all(prefix)?;
+        Ok(ZipWriter::new(inner))
+    }
+
     /// Set ZIP archive comment.
     pub fn set_comment<S>(&mut self, comment: S)
     where
@@ -353,33 +534,181 @@ impl<W: Write + io::Seek> ZipWriter<W> {
         self.comment = comment;
     }
 
-    /// Start a new file for with the requested options.
+    /// Always use the ZIP64 format, even when the archive is small enough to do without it.
+    ///
+    /// Files started afterwards are written as with [`FileOptions::large_file`], and the ZIP64
+    /// end of central directory records are written when the archive is finished. By default,
+    /// these records are only written when there are more than 65535 files, or when the central
+    /// directory ends beyond 4 GiB.
+    pub fn set_force_zip64(&mut self, force: bool) {
+        self.force_zip64 = force;
+    }
+
+    /// Encode the non-ASCII names of the files started afterwards with a legacy encoding,
+    /// instead of UTF-8.
+    ///
+    /// Some old archivers ignore the UTF-8 flag and decode the names with the code page of the
+    /// system. Starting a file whose name cannot be represented in `encoding`, or whose comment
+    /// is not ASCII, fails with an error. [`Encoding::Auto`] is the same as [`Encoding::Utf8`],
+    /// the default.
+    pub fn set_name_encoding(&mut self, encoding: Encoding) {
+        self.name_encoding = encoding;
+    }
+
+    /// Restrict the encryption of the files started afterwards.
+    ///
+    /// Starting a file whose encryption or password is not allowed by `policy` fails with
+    /// [`ZipError::PolicyViolation`], and nothing is written. Files added with the raw copy methods are written as
+    /// they are, without being checked.
+    pub fn set_encryption_policy(&mut self, policy: EncryptionPolicy) {
+        self.encryption_policy = policy;
+    }
+
+    /// Store identical files only once.
+    ///
+    /// When enabled, the uncompressed data of the files started afterwards is hashed. A file
+    /// with the same contents and compression method as an unencrypted file written before
+    /// is dropped from the output, and its central directory record points to the data of the
+    /// earlier file instead. Encrypted files are never shared, but they are still reported by
+    /// [`ZipWriter::duplicates`]. Files added with the raw copy methods are not deduplicated.
+    ///
+    /// Deduplicated files have no local file header of their own, so readers that check the
+    /// local header against the central directory, including streaming readers, only see
+    /// the first file. Identical contents are detected with the SHA-256 of the data along with
+    /// its CRC32 and size.
+    ///
+    /// The space taken by a duplicate file is reused by the entries written after it. Since the
+    /// output cannot be truncated, the archive is not compacted when less data follows the last
+    /// duplicate than it took: the rest of its data is left, unused, before the central
+    /// directory.
+    pub fn set_deduplicate(&mut self, deduplicate: bool) {
+        self.deduplicate = deduplicate;
+    }
+
+    /// Compress the `Zstd` files started afterwards with a dictionary.
+    ///
+    /// Dictionaries improve the compression of many small files with similar contents, which
+    /// are otherwise compressed independently. The dictionary must be in the zstd format, as
+    /// trained by [`ZipWriter::train_zstd_dictionary`] or `zstd --train`. It is written
+    /// right away in a `Stored` file named `.zstd-dictionaries/<id>`, and [`ZipArchive`] loads
+    /// it to decompress the files compressed with it. Other readers see a valid archive, but
+    /// cannot decompress these files. [`ZipArchive::extract`], [`ZipArchive::tree`] and
+    /// [`ZipArchive::metadata_index`] skip the dictionary files, which are listed otherwise.
+    ///
+    /// [`crate::zip::read::read_zipfile_from_stream`] cannot go back to a dictionary, so it
+    /// fails to decompress the files compressed with it.
+    ///
+    /// Files started with [`ZipWriter::start_file_with_extra_data`] are compressed without
+    /// the dictionary. Files copied with the raw copy methods keep using the dictionary they
+    /// were compressed with, which has to be copied as well.
+    #[cfg(feature = "zstd")]
+    pub fn set_zstd_dictionary(&mut self, dictionary: &[u8]) -> ZipResult<()> {
+        let id = zstd_dictionary::dictionary_id(dictionary)
+            .filter(|_| zstd::stream::raw::Encoder::with_dictionary(0, dictionary).is_ok())
+            .ok_or_else(|| {
+                ZipError::Io(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Invalid zstd dictionary",
+                ))
+            })?;
+
+        let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+        self.start_file(zstd_dictionary::name(id), options)?;
+        self.write_all(dictionary)?;
+        self.finish_file()?;
+        self.zstd_dictionary = Some((id, dictionary.to_vec()));
+        Ok(())
+    }
+
+    /// Train a zstd dictionary of at most `max_size` bytes on `samples`, typically the
+    /// contents of some of the files to add, and use it as with
+    /// [`ZipWriter::set_zstd_dictionary`].
+    #[cfg(feature = "zstd")]
+    pub fn train_zstd_dictionary<S: AsRef<[u8]>>(
+        &mut self,
+        samples: &[S],
+        max_size: usize,
+    ) -> ZipResult<()> {
+        let dictionary = zstd::dict::from_samples(samples, max_size)?;
+        self.set_zstd_dictionary(&dictionary)
+    }
+
+    /// Files that had the same contents as a file written before, when deduplicating
+    ///
+    /// Files are checked when they are finished, that is when the next file is started or
+    /// the archive is finished.
+    pub fn duplicates(&self) -> &[Duplicate] {
+        &self.duplicates
+    }
+
+    /// Start a new file for with the requested options, and the extra field to write in both
+    /// the local and central headers.
     fn start_entry<S>(
         &mut self,
         name: S,
-        options: FileOptions,
+        mut options: FileOptions,
         raw_values: Option<ZipRawValues>,
+        extra_field: Vec<u8>,
     ) -> ZipResult<()>
     where
         S: Into<String>,
     {
         self.finish_file()?;
 
+        let comment = options.comment.take().unwrap_or_default();
+        if comment.len() > spec::ZIP64_ENTRY_THR {
+            return Err(ZipError::Io(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "File comment exceeds 65535 bytes",
+            )));
+        }
+        if options.encrypt_with.is_some() {
+            self.encryption_policy.check(options.password_length)?;
+        }
+
+        let name = name.into();
+        let file_name_raw = match self.name_encoding {
+            Encoding::Utf8 | Encoding::Auto => Vec::new(),
+            _ if name.is_ascii() => Vec::new(),
+            encoding => {
+                if !comment.is_ascii() {
+                    return Err(ZipError::Io(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "File comment must be ASCII with a legacy name encoding",
+                    )));
+                }
+                encoding
+                    .encode(&name)
+                    .ok_or_else(|| {
+                        ZipError::Io(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            "File name cannot be represented in the name encoding",
+                        ))
+                    })?
+                    .into_owned()
+            }
+        };
+
         let raw_values = raw_values.unwrap_or(ZipRawValues {
             crc32: 0,
             compressed_size: 0,
             uncompressed_size: 0,
+            encrypted: false,
         });
 
         {
             let writer = self.inner.get_plain();
             let header_start = writer.stream_position()?;
 
-            let permissions = options.permissions.unwrap_or(0o100644);
+            let mut permissions = options.permissions.unwrap_or(0o100644);
+            let dos_attributes = options.dos_attributes.unwrap_or_default();
+            if dos_attributes.read_only && !dos_attributes.directory {
+                permissions &= !0o222;
+            }
             let mut file = ZipFileData {
                 system: System::Unix,
                 version_made_by: DEFAULT_VERSION,
-                encrypted: options.encrypt_with.is_some(),
+                encrypted: options.encrypt_with.is_some() || raw_values.encrypted,
                 using_data_descriptor: false,
                 compression_method: options.compression_method,
                 compression_level: options.compression_level,
@@ -387,18 +716,20 @@ impl<W: Write + io::Seek> ZipWriter<W> {
                 crc32: raw_values.crc32,
                 compressed_size: raw_values.compressed_size,
                 uncompressed_size: raw_values.uncompressed_size,
-                file_name: name.into(),
-                file_name_raw: Vec::new(), // Never used for saving
-                extra_field: Vec::new(),
-                file_comment: String::new(),
+                file_name: name,
+                // Only saved when it differs from the UTF-8 name, see `file_name_bytes`
+                file_name_raw,
+                extra_field,
+                file_comment: comment,
                 header_start,
                 data_start: AtomicU64::new(0),
                 central_header_start: 0,
-                external_attributes: permissions << 16,
-                large_file: options.large_file,
+                external_attributes: permissions << 16 | dos_attributes.bits() as u32,
+                large_file: options.large_file || self.force_zip64,
                 aes_mode: None,
             };
             write_local_file_header(writer, &file)?;
+            writer.write_all(&file.extra_field)?;
 
             let header_end = writer.stream_position()?;
             self.stats.start = header_end;
@@ -406,6 +737,7 @@ impl<W: Write + io::Seek> ZipWriter<W> {
 
             self.stats.bytes_written = 0;
             self.stats.hasher = Hasher::new();
+            self.stats.content_hasher = self.deduplicate.then(Sha256::new);
 
             self.files.push(file);
         }
@@ -424,7 +756,12 @@ impl<W: Write + io::Seek> ZipWriter<W> {
             // Implicitly calling [`ZipWriter::end_extra_data`] for empty files.
             self.end_extra_data()?;
         }
-        self.inner.switch_to(CompressionMethod::Stored, None)?;
+        self.inner.switch_to(
+            CompressionMethod::Stored,
+            None,
+            #[cfg(feature = "zstd")]
+            None,
+        )?;
         match core::mem::replace(&mut self.inner, GenericZipWriter::Closed) {
             GenericZipWriter::Storer(MaybeEncrypted::Encrypted(writer)) => {
                 let crc32 = self.stats.hasher.clone().finalize();
@@ -448,6 +785,10 @@ impl<W: Write + io::Seek> ZipWriter<W> {
 
             update_local_file_header(writer, file)?;
             writer.seek(io::SeekFrom::Start(file_end))?;
+
+            if let Some(content_hasher) = self.stats.content_hasher.take() {
+                self.deduplicate_last_file(content_hasher.finalize().into())?;
+            }
         }
 
         self.writing_to_file = false;
@@ -455,6 +796,57 @@ impl<W: Write + io::Seek> ZipWriter<W> {
         Ok(())
     }
 
+    /// Point the last file to the data of an earlier file with the same contents, if any.
+    fn deduplicate_last_file(&mut self, hash: [u8; 32]) -> ZipResult<()> {
+        let index = self.files.len() - 1;
+        let file = &self.files[index];
+        if file.uncompressed_size == 0 {
+            return Ok(());
+        }
+
+        let key = BlobKey {
+            hash,
+            crc32: file.crc32,
+            size: file.uncompressed_size,
+            compression_method: file.compression_method,
+            zstd_dictionary: zstd_dictionary::referenced_id(&file.extra_field),
+        };
+        let original = match self.blobs.entry(key) {
+            Entry::Occupied(entry) => &self.files[*entry.get()],
+            Entry::Vacant(entry) => {
+                if !file.encrypted {
+                    entry.insert(index);
+                }
+                return Ok(());
+            }
+        };
+        self.duplicates.push(Duplicate {
+            name: file.file_name.clone(),
+            original: original.file_name.clone(),
+            deduplicated: !file.encrypted,
+        });
+        if file.encrypted {
+            return Ok(());
+        }
+
+        let header_start = original.header_start;
+        let data_start = original.data_start.load();
+        let compressed_size = original.compressed_size;
+        let large_file = original.large_file;
+
+        // Overwrite the duplicate data with whatever comes next
+        let writer = self.inner.get_plain();
+        self.stream_end = self.stream_end.max(writer.stream_position()?);
+        writer.seek(io::SeekFrom::Start(file.header_start))?;
+
+        let file = &mut self.files[index];
+        file.header_start = header_start;
+        *file.data_start.get_mut() = data_start;
+        file.compressed_size = compressed_size;
+        file.large_file = large_file;
+        Ok(())
+    }
+
     /// Create a file in the archive and start writing its' contents.
     ///
     /// The data should be written using the [`io::Write`] implementation on this [`ZipWriter`]
@@ -466,9 +858,28 @@ impl<W: Write + io::Seek> ZipWriter<W> {
             options.permissions = Some(0o644);
         }
         *options.permissions.as_mut().unwrap() |= 0o100000;
-        self.start_entry(name, options, None)?;
-        self.inner
-            .switch_to(options.compression_method, options.compression_level)?;
+
+        #[cfg(feature = "zstd")]
+        let extra_field = match &self.zstd_dictionary {
+            Some((id, _)) if options.compression_method == CompressionMethod::Zstd => {
+                zstd_dictionary::extra_field(*id)
+            }
+            _ => Vec::new(),
+        };
+        #[cfg(not(feature = "zstd"))]
+        let extra_field = Vec::new();
+
+        let (compression_method, compression_level) =
+            (options.compression_method, options.compression_level);
+        self.start_entry(name, options, None, extra_field)?;
+        self.inner.switch_to(
+            compression_method,
+            compression_level,
+            #[cfg(feature = "zstd")]
+            self.zstd_dictionary
+                .as_ref()
+                .map(|(_, dictionary)| dictionary.as_slice()),
+        )?;
         self.writing_to_file = true;
         Ok(())
     }
@@ -543,7 +954,7 @@ impl<W: Write + io::Seek> ZipWriter<W> {
     ///     let options = FileOptions::default()
     ///         .compression_method(CompressionMethod::Stored);
     ///
-    ///     zip.start_file_with_extra_data("identical_extra_data.txt", options)?;
+    ///     zip.start_file_with_extra_data("identical_extra_data.txt", options.clone())?;
     ///     let extra_data = b"local and central extra data";
     ///     zip.write_u16::<LittleEndian>(0xbeef)?;
     ///     zip.write_u16::<LittleEndian>(extra_data.len() as u16)?;
@@ -593,7 +1004,7 @@ impl<W: Write + io::Seek> ZipWriter<W> {
             options.permissions = Some(0o644);
         }
         *options.permissions.as_mut().unwrap() |= 0o100000;
-        self.start_entry(name, options, None)?;
+        self.start_entry(name, options, None, Vec::new())?;
         self.writing_to_file = true;
         self.writing_to_extra_field = true;
         Ok(self.files.last().unwrap().data_start.load())
@@ -645,8 +1056,12 @@ impl<W: Write + io::Seek> ZipWriter<W> {
             writer.write_u16::<LittleEndian>(extra_field_length)?;
             writer.seek(io::SeekFrom::Start(header_end))?;
 
-            self.inner
-                .switch_to(file.compression_method, file.compression_level)?;
+            self.inner.switch_to(
+                file.compression_method,
+                file.compression_level,
+                #[cfg(feature = "zstd")]
+                None,
+            )?;
         }
 
         self.writing_to_extra_field = false;
@@ -680,36 +1095,111 @@ impl<W: Write + io::Seek> ZipWriter<W> {
     ///     Ok(())
     /// }
     /// ```
-    pub fn raw_copy_file_rename<S>(&mut self, mut file: ZipFile, name: S) -> ZipResult<()>
+    pub fn raw_copy_file_rename<S>(&mut self, file: ZipFile, name: S) -> ZipResult<()>
     where
         S: Into<String>,
     {
+        self.raw_copy_file_rename_with_progress(file, name, &mut ())
+    }
+
+    /// Add a new file using the already compressed data from a ZIP file being read and renames
+    /// it, like [`ZipWriter::raw_copy_file_rename`], reporting the copied compressed data to
+    /// `progress`
+    ///
+    /// If the copy is cancelled, the new file is dropped from the archive.
+    pub fn raw_copy_file_rename_with_progress<S>(
+        &mut self,
+        file: ZipFile,
+        name: S,
+        progress: &mut dyn Progress,
+    ) -> ZipResult<()>
+    where
+        S: Into<String>,
+    {
+        self.raw_copy_entry(file, name.into(), None, progress)
+    }
+
+    /// Raw copy `file` as `name`, with the comment of the file unless another one is given
+    fn raw_copy_entry(
+        &mut self,
+        mut file: ZipFile,
+        name: String,
+        comment: Option<String>,
+        progress: &mut dyn Progress,
+    ) -> ZipResult<()> {
+        if file.aes_encrypted() {
+            // The AES extra field and compression method would have to be copied as well
+            return Err(ZipError::UnsupportedArchive(
+                "Copying AES encrypted files is not supported",
+            ));
+        }
+        progress::start_entry(progress, &name, Some(file.compressed_size()))?;
+
         let mut options = FileOptions::default()
             .large_file(file.compressed_size().max(file.size()) > spec::ZIP64_BYTES_THR)
             .last_modified_time(file.last_modified())
-            .compression_method(file.compression());
+            .compression_method(file.compression())
+            .comment(comment.unwrap_or_else(|| file.comment().to_owned()));
         if let Some(perms) = file.unix_mode() {
             options = options.unix_permissions(perms);
         }
+        if let Some(attributes) = file.dos_attributes() {
+            options = options.dos_attributes(attributes);
+        }
 
         let raw_values = ZipRawValues {
             crc32: file.crc32(),
             compressed_size: file.compressed_size(),
             uncompressed_size: file.size(),
+            encrypted: file.encrypted(),
         };
 
-        self.start_entry(name, options, Some(raw_values))?;
+        // Keep the reference to the zstd dictionary, which has to be copied as well
+        let extra_field = zstd_dictionary::referenced_id(file.extra_data())
+            .map(zstd_dictionary::extra_field)
+            .unwrap_or_default();
+
+        self.start_entry(name, options, Some(raw_values), extra_field)?;
         self.writing_to_file = true;
         self.writing_raw = true;
 
-        io::copy(file.get_raw_reader(), self)?;
+        self.copy_with_progress(file.get_raw_reader(), progress)
+    }
+
+    /// Copy `reader` to the current file, dropping the file if `progress` cancels the copy.
+    fn copy_with_progress(
+        &mut self,
+        reader: &mut dyn Read,
+        progress: &mut dyn Progress,
+    ) -> ZipResult<()> {
+        match progress::copy(reader, self, progress) {
+            Ok(_) => Ok(()),
+            Err(ZipError::Cancelled) => {
+                self.discard_last_file()?;
+                Err(ZipError::Cancelled)
+            }
+            Err(e) => Err(e),
+        }
+    }
 
+    /// Remove the file being written from the archive.
+    fn discard_last_file(&mut self) -> ZipResult<()> {
+        // The file is dropped anyway, so it must not be deduplicated
+        self.stats.content_hasher = None;
+        self.finish_file()?;
+        if let Some(file) = self.files.pop() {
+            let writer = self.inner.get_plain();
+            self.stream_end = self.stream_end.max(writer.stream_position()?);
+            writer.seek(io::SeekFrom::Start(file.header_start))?;
+            self.writing_raw = true; // avoid recomputing the previous file's header
+        }
         Ok(())
     }
 
     /// Add a new file using the already compressed data from a ZIP file being read, this allows faster
     /// copies of the `ZipFile` since there is no need to decompress and compress it again. Any `ZipFile`
-    /// metadata is copied and not checked, for example the file CRC.
+    /// metadata is copied and not checked, for example the file CRC. Files encrypted with ZipCrypto
+    /// stay encrypted, while copying files encrypted with AES is not supported.
     ///
     /// ```no_run
     /// use std::fs::File;
@@ -735,6 +1225,83 @@ impl<W: Write + io::Seek> ZipWriter<W> {
         self.raw_copy_file_rename(file, name)
     }
 
+    /// Add a new file from a ZIP file being read, compressed and encrypted as requested by `options`.
+    ///
+    /// The compressed data is copied as with [`ZipWriter::raw_copy_file`] when neither the
+    /// source nor `options` use encryption, `options` doesn't request a compression level, and the
+    /// compression method is the same. Otherwise the file is decompressed and compressed again,
+    /// so encrypted files must be opened with a password, e.g. with
+    /// [`ZipArchive::by_index_decrypt`]. Files opened with [`ZipArchive::by_index_raw`] cannot
+    /// be decompressed, and fail with an error in that case.
+    ///
+    /// The name, modification time and permissions of the file are kept, and so is its comment
+    /// unless `options` sets another one, which may be empty.
+    ///
+    /// This writer cannot encrypt with AES, so files can only be encrypted again with the legacy
+    /// ZipCrypto algorithm, see [`FileOptions::with_deprecated_encryption`]. The example below
+    /// decrypts ZipCrypto files and writes them unencrypted, compressed with Zstd.
+    ///
+    /// ```no_run
+    /// use std::io::{Read, Seek, Write};
+    /// use zip::write::FileOptions;
+    /// use zip::{CompressionMethod, ZipArchive, ZipWriter};
+    ///
+    /// fn migrate<R, W>(
+    ///     src: &mut ZipArchive<R>,
+    ///     dst: &mut ZipWriter<W>,
+    ///     password: &[u8],
+    /// ) -> zip::result::ZipResult<()>
+    /// where
+    ///     R: Read + Seek,
+    ///     W: Write + Seek,
+    /// {
+    ///     let options = FileOptions::default().compression_method(CompressionMethod::Zstd);
+    ///     for i in 0..src.len() {
+    ///         let file = src.by_index_decrypt(i, password)?.expect("invalid password");
+    ///         dst.transcode_file(file, options.clone())?;
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn transcode_file(&mut self, mut file: ZipFile, options: FileOptions) -> ZipResult<()> {
+        if !file.encrypted()
+            && options.encrypt_with.is_none()
+            && options.compression_level.is_none()
+            && file.compression() == options.compression_method
+        {
+            let name = file.name().to_owned();
+            return self.raw_copy_entry(file, name, options.comment, &mut ());
+        }
+
+        let large_file = options.large_file || file.size() > spec::ZIP64_BYTES_THR;
+        let mut options = options
+            .last_modified_time(file.last_modified())
+            .large_file(large_file);
+        if options.comment.is_none() {
+            options = options.comment(file.comment());
+        }
+        if let Some(perms) = file.unix_mode() {
+            options = options.unix_permissions(perms);
+        }
+        if let Some(attributes) = file.dos_attributes() {
+            options = options.dos_attributes(attributes);
+        }
+
+        let name = file.name().to_owned();
+        if file.is_dir() {
+            self.add_directory(name, options)
+        } else if file.is_raw() {
+            Err(ZipError::Io(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Files opened with by_index_raw cannot be compressed or encrypted again",
+            )))
+        } else {
+            self.start_file(name, options)?;
+            io::copy(&mut file, self)?;
+            Ok(())
+        }
+    }
+
     /// Add a directory entry.
     ///
     /// As directories have no content, you must not call [`ZipWriter::write`] before adding a new file.
@@ -746,6 +1313,9 @@ impl<W: Write + io::Seek> ZipWriter<W> {
             options.permissions = Some(0o755);
         }
         *options.permissions.as_mut().unwrap() |= 0o40000;
+        if let Some(attributes) = options.dos_attributes.as_mut() {
+            attributes.directory = true;
+        }
         options.compression_method = CompressionMethod::Stored;
 
         let name_as_string = name.into();
@@ -755,7 +1325,7 @@ impl<W: Write + io::Seek> ZipWriter<W> {
             _ => name_as_string + "/",
         };
 
-        self.start_entry(name_with_slash, options, None)?;
+        self.start_entry(name_with_slash, options, None, Vec::new())?;
         self.writing_to_file = false;
         Ok(())
     }
@@ -776,6 +1346,69 @@ impl<W: Write + io::Seek> ZipWriter<W> {
         self.add_directory(path_to_string(path), options)
     }
 
+    /// Add a file, or a directory with all its contents, from the file system under `name`.
+    ///
+    /// The entries of a directory are added in the order of their names, below `name`.
+    /// On Unix, the permissions of the files and directories are kept. Symbolic links are not
+    /// followed, but stored as links with [`ZipWriter::add_symlink`], so links to directories
+    /// and link loops don't add the same files again.
+    pub fn add_path_recursive<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        name: &str,
+        options: FileOptions,
+    ) -> ZipResult<()> {
+        self.add_path_recursive_with_progress(path, name, options, &mut ())
+    }
+
+    /// Add a file or a directory with all its contents like [`ZipWriter::add_path_recursive`],
+    /// reporting every entry and the data read from the files to `progress`
+    ///
+    /// If the operation is cancelled, the file being written is dropped from the archive, but
+    /// the entries added before are kept.
+    pub fn add_path_recursive_with_progress<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        name: &str,
+        options: FileOptions,
+        progress: &mut dyn Progress,
+    ) -> ZipResult<()> {
+        let path = path.as_ref();
+        let metadata = fs::symlink_metadata(path)?;
+        #[cfg(unix)]
+        let options = {
+            use std::os::unix::fs::PermissionsExt;
+            options.unix_permissions(metadata.permissions().mode())
+        };
+
+        if metadata.file_type().is_symlink() {
+            let target = fs::read_link(path)?;
+            progress::start_entry(progress, name, Some(0))?;
+            self.add_symlink(name, target.to_string_lossy(), options)?;
+        } else if metadata.is_dir() {
+            let name = name.trim_end_matches('/');
+            progress::start_entry(progress, &format!("{name}/"), Some(0))?;
+            self.add_directory(name, options.clone())?;
+
+            let mut children = fs::read_dir(path)?.collect::<Result<Vec<_>, _>>()?;
+            children.sort_by_key(|child| child.file_name());
+            for child in children {
+                let child_name = format!("{name}/{}", child.file_name().to_string_lossy());
+                self.add_path_recursive_with_progress(
+                    child.path(),
+                    &child_name,
+                    options.clone(),
+                    progress,
+                )?;
+            }
+        } else {
+            progress::start_entry(progress, name, Some(metadata.len()))?;
+            self.start_file(name, options)?;
+            self.copy_with_progress(&mut fs::File::open(path)?, progress)?;
+        }
+        Ok(())
+    }
+
     /// Finish the last file and write all other zip-structures
     ///
     /// This will return the writer, but one should normally not append any data to the end of the file.
@@ -816,7 +1449,7 @@ impl<W: Write + io::Seek> ZipWriter<W> {
         // likely wastes space. So always store.
         options.compression_method = CompressionMethod::Stored;
 
-        self.start_entry(name, options, None)?;
+        self.start_entry(name, options, None, Vec::new())?;
         self.writing_to_file = true;
         self.write_all(target.into().as_bytes())?;
         self.writing_to_file = false;
@@ -830,15 +1463,26 @@ impl<W: Write + io::Seek> ZipWriter<W> {
         {
             let writer = self.inner.get_plain();
 
-            let central_start = writer.stream_position()?;
+            let mut central_directory = Vec::new();
             for file in self.files.iter() {
-                write_central_directory_header(writer, file)?;
+                write_central_directory_header(&mut central_directory, file)?;
+            }
+            let central_size = central_directory.len() as u64;
+
+            // Deduplicated or discarded files may have left data after the current position,
+            // which is skipped so that the archive still ends with the end of central directory record.
+            let mut central_start = writer.stream_position()?;
+            let zip64 = self.force_zip64
+                || self.files.len() > spec::ZIP64_ENTRY_THR
+                || central_size.max(central_start.max(self.stream_end)) > spec::ZIP64_BYTES_THR;
+            let footer_size = 22 + self.comment.len() as u64 + if zip64 { 56 + 20 } else { 0 };
+            if central_start + central_size + footer_size < self.stream_end {
+                central_start = self.stream_end - central_size - footer_size;
+                writer.seek(io::SeekFrom::Start(central_start))?;
             }
-            let central_size = writer.stream_position()? - central_start;
+            writer.write_all(&central_directory)?;
 
-            if self.files.len() > spec::ZIP64_ENTRY_THR
-                || central_size.max(central_start) > spec::ZIP64_BYTES_THR
-            {
+            if zip64 {
                 let zip64_footer = spec::Zip64CentralDirectoryEnd {
                     version_made_by: DEFAULT_VERSION as u16,
                     version_needed_to_extract: DEFAULT_VERSION as u16,
@@ -894,6 +1538,7 @@ impl<W: Write + io::Seek> GenericZipWriter<W> {
         &mut self,
         compression: CompressionMethod,
         compression_level: Option<i32>,
+        #[cfg(feature = "zstd")] zstd_dictionary: Option<&[u8]>,
     ) -> ZipResult<()> {
         match self.current_compression() {
             Some(method) if method == compression => return Ok(()),
@@ -978,19 +1623,25 @@ impl<W: Write + io::Seek> GenericZipWriter<W> {
                     ))
                 }
                 #[cfg(feature = "zstd")]
-                CompressionMethod::Zstd => GenericZipWriter::Zstd(
-                    ZstdEncoder::new(
-                        bare,
-                        clamp_opt(
-                            compression_level.unwrap_or(zstd::DEFAULT_COMPRESSION_LEVEL),
-                            zstd::compression_level_range(),
-                        )
-                        .ok_or(ZipError::UnsupportedArchive(
-                            "Unsupported compression level",
-                        ))?,
+                CompressionMethod::Zstd => {
+                    let level = clamp_opt(
+                        compression_level.unwrap_or(zstd::DEFAULT_COMPRESSION_LEVEL),
+                        zstd::compression_level_range(),
                     )
-                    .unwrap(),
-                ),
+                    .ok_or(ZipError::UnsupportedArchive(
+                        "Unsupported compression level",
+                    ))?;
+                    // Dictionaries are checked by `ZipWriter::set_zstd_dictionary`
+                    GenericZipWriter::Zstd(
+                        match zstd_dictionary {
+                            Some(dictionary) => {
+                                ZstdEncoder::with_dictionary(bare, level, dictionary)
+                            }
+                            None => ZstdEncoder::new(bare, level),
+                        }
+                        .unwrap(),
+                    )
+                }
                 CompressionMethod::Unsupported(..) => {
                     return Err(ZipError::UnsupportedArchive("Unsupported compression"))
                 }
@@ -1086,18 +1737,47 @@ fn clamp_opt<T: Ord + Copy>(value: T, range: std::ops::RangeInclusive<T>) -> Opt
     }
 }
 
+/// The bytes of the file name to save, which are only encoded as UTF-8 when the raw name is
+/// empty or the same
+fn file_name_bytes(file: &ZipFileData) -> &[u8] {
+    if file.file_name_raw.is_empty() {
+        file.file_name.as_bytes()
+    } else {
+        &file.file_name_raw
+    }
+}
+
+fn has_legacy_name(file: &ZipFileData) -> bool {
+    file_name_bytes(file) != file.file_name.as_bytes()
+}
+
+/// The bytes of the file comment to save, in the encoding of the name
+///
+/// New files with a legacy name have ASCII comments, so the non-ASCII comments with a legacy
+/// name come from [`ZipWriter::new_append`], which decodes both as CP437.
+fn file_comment_bytes(file: &ZipFileData) -> Cow<'_, [u8]> {
+    if has_legacy_name(file) {
+        if let Some(bytes) = Encoding::Cp437.encode(&file.file_comment) {
+            return bytes;
+        }
+    }
+    file.file_comment.as_bytes().into()
+}
+
+fn general_purpose_flag(file: &ZipFileData) -> u16 {
+    // The language encoding flag applies to both the file name and the comment
+    let ascii = file.file_name.is_ascii() && file.file_comment.is_ascii();
+    let utf8 = !ascii && !has_legacy_name(file);
+    (if utf8 { 1u16 << 11 } else { 0 }) | if file.encrypted { 1u16 << 0 } else { 0 }
+}
+
 fn write_local_file_header<T: Write>(writer: &mut T, file: &ZipFileData) -> ZipResult<()> {
     // local file header signature
     writer.write_u32::<LittleEndian>(spec::LOCAL_FILE_HEADER_SIGNATURE)?;
     // version needed to extract
     writer.write_u16::<LittleEndian>(file.version_needed())?;
     // general purpose bit flag
-    let flag = if !file.file_name.is_ascii() {
-        1u16 << 11
-    } else {
-        0
-    } | if file.encrypted { 1u16 << 0 } else { 0 };
-    writer.write_u16::<LittleEndian>(flag)?;
+    writer.write_u16::<LittleEndian>(general_purpose_flag(file))?;
     // Compression method
     #[allow(deprecated)]
     writer.write_u16::<LittleEndian>(file.compression_method.to_u16())?;
@@ -1115,12 +1795,12 @@ fn write_local_file_header<T: Write>(writer: &mut T, file: &ZipFileData) -> ZipR
         writer.write_u32::<LittleEndian>(file.uncompressed_size as u32)?;
     }
     // file name length
-    writer.write_u16::<LittleEndian>(file.file_name.as_bytes().len() as u16)?;
+    writer.write_u16::<LittleEndian>(file_name_bytes(file).len() as u16)?;
     // extra field length
     let extra_field_length = if file.large_file { 20 } else { 0 } + file.extra_field.len() as u16;
     writer.write_u16::<LittleEndian>(extra_field_length)?;
     // file name
-    writer.write_all(file.file_name.as_bytes())?;
+    writer.write_all(file_name_bytes(file))?;
     // zip64 extra field
     if file.large_file {
         write_local_zip64_extra_field(writer, file)?;
@@ -1167,12 +1847,7 @@ fn write_central_directory_header<T: Write>(writer: &mut T, file: &ZipFileData)
     // version needed to extract
     writer.write_u16::<LittleEndian>(file.version_needed())?;
     // general puprose bit flag
-    let flag = if !file.file_name.is_ascii() {
-        1u16 << 11
-    } else {
-        0
-    } | if file.encrypted { 1u16 << 0 } else { 0 };
-    writer.write_u16::<LittleEndian>(flag)?;
+    writer.write_u16::<LittleEndian>(general_purpose_flag(file))?;
     // compression method
     #[allow(deprecated)]
     writer.write_u16::<LittleEndian>(file.compression_method.to_u16())?;
@@ -1186,11 +1861,12 @@ fn write_central_directory_header<T: Write>(writer: &mut T, file: &ZipFileData)
     // uncompressed size
     writer.write_u32::<LittleEndian>(file.uncompressed_size.min(spec::ZIP64_BYTES_THR) as u32)?;
     // file name length
-    writer.write_u16::<LittleEndian>(file.file_name.as_bytes().len() as u16)?;
+    writer.write_u16::<LittleEndian>(file_name_bytes(file).len() as u16)?;
     // extra field length
     writer.write_u16::<LittleEndian>(zip64_extra_field_length + file.extra_field.len() as u16)?;
     // file comment length
-    writer.write_u16::<LittleEndian>(0)?;
+    let file_comment = file_comment_bytes(file);
+    writer.write_u16::<LittleEndian>(file_comment.len() as u16)?;
     // disk number start
     writer.write_u16::<LittleEndian>(0)?;
     // internal file attribytes
@@ -1200,13 +1876,13 @@ fn write_central_directory_header<T: Write>(writer: &mut T, file: &ZipFileData)
     // relative offset of local header
     writer.write_u32::<LittleEndian>(file.header_start.min(spec::ZIP64_BYTES_THR) as u32)?;
     // file name
-    writer.write_all(file.file_name.as_bytes())?;
+    writer.write_all(file_name_bytes(file))?;
     // zip64 extra field
     writer.write_all(&zip64_extra_field[..zip64_extra_field_length as usize])?;
     // extra field
     writer.write_all(&file.extra_field)?;
     // file comment
-    // <none>
+    writer.write_all(&file_comment)?;
 
     Ok(())
 }
@@ -1281,7 +1957,7 @@ fn update_local_zip64_extra_field<T: Write + io::Seek>(
     writer: &mut T,
     file: &ZipFileData,
 ) -> ZipResult<()> {
-    let zip64_extra_field = file.header_start + 30 + file.file_name.as_bytes().len() as u64;
+    let zip64_extra_field = file.header_start + 30 + file_name_bytes(file).len() as u64;
     writer.seek(io::SeekFrom::Start(zip64_extra_field + 4))?;
     writer.write_u64::<LittleEndian>(file.uncompressed_size)?;
     writer.write_u64::<LittleEndian>(file.compressed_size)?;
@@ -1346,8 +2022,9 @@ mod test {
     use super::{FileOptions, ZipWriter};
     use crate::compression::CompressionMethod;
     use crate::types::DateTime;
+    use crate::zip::read::ZipArchive;
     use std::io;
-    use std::io::Write;
+    use std::io::{Read, Write};
 
     #[test]
     fn write_empty_zip() {
@@ -1368,6 +2045,92 @@ mod test {
         assert_eq!(options.permissions, Some(0o777));
     }
 
+    #[test]
+    fn dos_attributes_roundtrip() {
+        use crate::zip::types::DosAttributes;
+
+        let attributes = DosAttributes {
+            read_only: true,
+            hidden: true,
+            archive: true,
+            ..DosAttributes::default()
+        };
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        let options = FileOptions::default().dos_attributes(attributes);
+        writer.start_file("hidden.txt", options.clone()).unwrap();
+        writer.add_directory("dir", options).unwrap();
+        writer
+            .start_file("plain.txt", FileOptions::default())
+            .unwrap();
+        let mut source = ZipArchive::new(writer.finish().unwrap()).unwrap();
+
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        writer
+            .raw_copy_file(source.by_name("hidden.txt").unwrap())
+            .unwrap();
+        let mut archive = ZipArchive::new(writer.finish().unwrap()).unwrap();
+
+        let file = archive.by_name("hidden.txt").unwrap();
+        assert_eq!(file.dos_attributes(), Some(attributes));
+        assert_eq!(file.unix_mode().map(|mode| mode & 0o777), Some(0o444));
+        drop(file);
+        let dir = source.by_name("dir/").unwrap();
+        assert_eq!(
+            dir.dos_attributes().map(|attributes| attributes.bits()),
+            Some(0x33)
+        );
+        assert_eq!(dir.unix_mode(), Some(0o40755));
+        drop(dir);
+        assert_eq!(source.by_name("plain.txt").unwrap().dos_attributes(), None);
+    }
+
+    #[test]
+    fn encryption_policy() {
+        use super::EncryptionPolicy;
+        use crate::zip::result::{PolicyViolation, ZipError};
+
+        let rejected = |policy: EncryptionPolicy, password: &[u8]| {
+            let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+            writer.set_encryption_policy(policy);
+            writer
+                .start_file("plain.txt", FileOptions::default())
+                .unwrap();
+            let options = FileOptions::default().with_deprecated_encryption(password);
+            match writer.start_file("secret.txt", options) {
+                Ok(()) => None,
+                Err(ZipError::PolicyViolation(violation)) => {
+                    // The rejected file is not added to the archive
+                    let archive = ZipArchive::new(writer.finish().unwrap()).unwrap();
+                    assert_eq!(archive.file_names().collect::<Vec<_>>(), ["plain.txt"]);
+                    Some(violation)
+                }
+                Err(e) => panic!("unexpected error: {:?}", e),
+            }
+        };
+
+        assert_eq!(rejected(EncryptionPolicy::default(), b""), None);
+        let min_length = EncryptionPolicy {
+            min_password_length: 12,
+            ..EncryptionPolicy::default()
+        };
+        assert_eq!(rejected(min_length, b"correct horse"), None);
+        assert_eq!(
+            rejected(min_length, b"hunter2"),
+            Some(PolicyViolation::PasswordTooShort {
+                length: 7,
+                min_length: 12,
+            })
+        );
+        let no_zip_crypto = EncryptionPolicy {
+            forbid_zip_crypto: true,
+            ..EncryptionPolicy::default()
+        };
+        assert_eq!(
+            rejected(no_zip_crypto, b"correct horse"),
+            Some(PolicyViolation::ZipCryptoForbidden)
+        );
+    }
+
     #[test]
     fn write_zip_dir() {
         let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
@@ -1425,6 +2188,29 @@ mod test {
         );
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn add_path_recursive_symlink_loop() {
+        let directory =
+            std::env::temp_dir().join(format!("zip_symlink_loop_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("file.txt"), "contents").unwrap();
+        std::os::unix::fs::symlink(".", directory.join("loop")).unwrap();
+
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        let result = writer.add_path_recursive(&directory, "root", FileOptions::default());
+        std::fs::remove_dir_all(&directory).unwrap();
+        result.unwrap();
+
+        let mut archive = ZipArchive::new(writer.finish().unwrap()).unwrap();
+        assert_eq!(archive.len(), 3);
+        let mut link = archive.by_name("root/loop").unwrap();
+        assert_eq!(link.unix_mode().unwrap() & 0o170000, 0o120000);
+        let mut target = String::new();
+        link.read_to_string(&mut target).unwrap();
+        assert_eq!(target, ".");
+    }
+
     #[test]
     fn write_symlink_wonky_paths() {
         let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
@@ -1467,6 +2253,9 @@ mod test {
             permissions: Some(33188),
             large_file: false,
             encrypt_with: None,
+            password_length: 0,
+            comment: None,
+            dos_attributes: None,
         };
         writer.start_file("mimetype", options).unwrap();
         writer
@@ -1480,6 +2269,214 @@ mod test {
         assert_eq!(result.get_ref(), &v);
     }
 
+    #[test]
+    fn write_file_comment() {
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        let options = FileOptions::default()
+            .compression_method(CompressionMethod::Stored)
+            .comment("ascii comment");
+        writer.start_file("ascii.txt", options.clone()).unwrap();
+        writer.write_all(b"ascii").unwrap();
+        writer
+            .start_file("utf8.txt", options.clone().comment("Kommentar über UTF-8"))
+            .unwrap();
+        writer.write_all(b"utf8").unwrap();
+        writer.add_directory("dir", options).unwrap();
+        let result = writer.finish().unwrap();
+        let bytes = result.get_ref().clone();
+
+        let mut archive = ZipArchive::new(result).unwrap();
+        assert_eq!(
+            archive.by_name("ascii.txt").unwrap().comment(),
+            "ascii comment"
+        );
+        assert_eq!(
+            archive.by_name("utf8.txt").unwrap().comment(),
+            "Kommentar über UTF-8"
+        );
+        assert_eq!(archive.by_name("dir/").unwrap().comment(), "ascii comment");
+
+        // Only the file with the non-ASCII comment has the language encoding flag
+        let mut utf8_flag = |name: &str| {
+            let start = archive.by_name(name).unwrap().central_header_start() as usize;
+            u16::from_le_bytes([bytes[start + 8], bytes[start + 9]]) & (1 << 11) != 0
+        };
+        assert!(!utf8_flag("ascii.txt"));
+        assert!(utf8_flag("utf8.txt"));
+    }
+
+    #[test]
+    fn write_archive_comment() {
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        writer.set_comment("Archiv-Kommentar über UTF-8");
+        writer
+            .start_file("file.txt", FileOptions::default().comment("file"))
+            .unwrap();
+        let result = writer.finish().unwrap();
+
+        let mut archive = ZipArchive::new(result).unwrap();
+        assert_eq!(archive.comment(), "Archiv-Kommentar über UTF-8".as_bytes());
+        assert_eq!(archive.by_index(0).unwrap().comment(), "file");
+    }
+
+    #[test]
+    fn write_file_comment_too_long() {
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        let comment = "x".repeat(u16::MAX as usize + 1);
+        assert!(writer
+            .start_file("file.txt", FileOptions::default().comment(&comment))
+            .is_err());
+    }
+
+    fn transcode_source() -> ZipArchive<io::Cursor<Vec<u8>>> {
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        let options = FileOptions::default()
+            .compression_method(CompressionMethod::Stored)
+            .last_modified_time(DateTime::from_date_and_time(2018, 8, 15, 20, 45, 6).unwrap())
+            .unix_permissions(0o600)
+            .comment("kept");
+        writer.start_file("plain.txt", options.clone()).unwrap();
+        writer.write_all(b"plain text").unwrap();
+        writer
+            .start_file(
+                "secret.txt",
+                options.with_deprecated_encryption(b"password"),
+            )
+            .unwrap();
+        writer.write_all(b"secret text").unwrap();
+        ZipArchive::new(writer.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn transcode_file_raw_copy() {
+        let mut source = transcode_source();
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+        writer
+            .transcode_file(source.by_name("plain.txt").unwrap(), options)
+            .unwrap();
+        let mut archive = ZipArchive::new(writer.finish().unwrap()).unwrap();
+
+        let file = archive.by_name("plain.txt").unwrap();
+        assert_eq!(file.compression(), CompressionMethod::Stored);
+        assert_eq!(file.unix_mode().map(|mode| mode & 0o777), Some(0o600));
+        assert_eq!(file.last_modified().year(), 2018);
+        assert_eq!(file.comment(), "kept");
+    }
+
+    #[test]
+    fn transcode_file_raw_source() {
+        let mut source = transcode_source();
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+        writer
+            .transcode_file(source.by_index_raw(0).unwrap(), stored.clone())
+            .unwrap();
+        // The raw data of an encrypted file, or with another method, would be written as it is
+        assert!(writer
+            .transcode_file(source.by_index_raw(1).unwrap(), stored.clone())
+            .is_err());
+        assert!(writer
+            .transcode_file(
+                source.by_index_raw(0).unwrap(),
+                stored.compression_level(Some(1))
+            )
+            .is_err());
+
+        let archive = ZipArchive::new(writer.finish().unwrap()).unwrap();
+        assert_eq!(archive.file_names().collect::<Vec<_>>(), ["plain.txt"]);
+    }
+
+    #[test]
+    fn transcode_file_empty_comment() {
+        let mut source = transcode_source();
+        // Raw copied, then recompressed
+        for method in [CompressionMethod::Stored, CompressionMethod::Deflated] {
+            if !crate::zip::compression::SUPPORTED_COMPRESSION_METHODS.contains(&method) {
+                continue;
+            }
+            let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+            let options = FileOptions::default()
+                .compression_method(method)
+                .comment("");
+            writer
+                .transcode_file(source.by_name("plain.txt").unwrap(), options)
+                .unwrap();
+            let mut archive = ZipArchive::new(writer.finish().unwrap()).unwrap();
+            assert_eq!(archive.by_name("plain.txt").unwrap().comment(), "");
+        }
+    }
+
+    #[test]
+    fn transcode_file_decrypt() {
+        let mut source = transcode_source();
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+        let file = source
+            .by_name_decrypt("secret.txt", b"password")
+            .unwrap()
+            .unwrap();
+        writer.transcode_file(file, options).unwrap();
+        let mut archive = ZipArchive::new(writer.finish().unwrap()).unwrap();
+
+        let mut file = archive.by_name("secret.txt").unwrap();
+        assert!(!file.encrypted());
+        assert_eq!(file.compressed_size(), 11);
+        assert_eq!(file.unix_mode(), Some(0o100600));
+        assert_eq!(file.comment(), "kept");
+        let mut contents = String::new();
+        file.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "secret text");
+    }
+
+    #[test]
+    fn raw_copy_encrypted_file() {
+        let mut source = transcode_source();
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        writer
+            .raw_copy_file(source.by_index_raw(1).unwrap())
+            .unwrap();
+        let mut archive = ZipArchive::new(writer.finish().unwrap()).unwrap();
+
+        assert!(archive.by_name("secret.txt").is_err());
+        let mut file = archive
+            .by_name_decrypt("secret.txt", b"password")
+            .unwrap()
+            .unwrap();
+        assert!(file.encrypted());
+        let mut contents = String::new();
+        file.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "secret text");
+    }
+
+    #[cfg(any(
+        feature = "deflate",
+        feature = "deflate-miniz",
+        feature = "deflate-zlib"
+    ))]
+    #[test]
+    fn transcode_file_recompress() {
+        let mut source = transcode_source();
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        let options = FileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .with_deprecated_encryption(b"other");
+        writer
+            .transcode_file(source.by_name("plain.txt").unwrap(), options)
+            .unwrap();
+        let mut archive = ZipArchive::new(writer.finish().unwrap()).unwrap();
+
+        let mut file = archive
+            .by_name_decrypt("plain.txt", b"other")
+            .unwrap()
+            .unwrap();
+        assert!(file.encrypted());
+        assert_eq!(file.compression(), CompressionMethod::Deflated);
+        let mut contents = String::new();
+        file.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "plain text");
+    }
+
     #[test]
     fn path_to_string() {
         let mut path = std::path::PathBuf::new();
@@ -1494,6 +2491,236 @@ mod test {
         let path_str = super::path_to_string(&path);
         assert_eq!(path_str, "windows/system32");
     }
+
+    fn has_zip64_end(archive: &[u8]) -> bool {
+        let signature = super::spec::ZIP64_CENTRAL_DIRECTORY_END_SIGNATURE.to_le_bytes();
+        archive.windows(4).any(|window| window == signature)
+    }
+
+    #[test]
+    fn write_more_than_65535_files() {
+        const COUNT: usize = 70_000;
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+        for i in 0..COUNT {
+            writer.start_file(format!("{i}"), options.clone()).unwrap();
+            writer.write_all(&[i as u8]).unwrap();
+        }
+        let result = writer.finish().unwrap().into_inner();
+        assert!(has_zip64_end(&result));
+
+        let mut archive = ZipArchive::new(io::Cursor::new(result)).unwrap();
+        assert_eq!(archive.len(), COUNT);
+        for i in [0, 65_535, COUNT - 1] {
+            let mut file = archive.by_name(&format!("{i}")).unwrap();
+            let mut contents = Vec::new();
+            file.read_to_end(&mut contents).unwrap();
+            assert_eq!(contents, [i as u8]);
+        }
+    }
+
+    /// Stream whose first `start` bytes are zeros that are never stored
+    struct SparseCursor {
+        start: u64,
+        data: Vec<u8>,
+        position: u64,
+    }
+
+    impl Write for SparseCursor {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let offset = self.position.checked_sub(self.start).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, "Write into sparse area")
+            })? as usize;
+            if self.data.len() < offset + buf.len() {
+                self.data.resize(offset + buf.len(), 0);
+            }
+            self.data[offset..offset + buf.len()].copy_from_slice(buf);
+            self.position += buf.len() as u64;
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Read for SparseCursor {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let read = if self.position < self.start {
+                let zeros = buf.len().min((self.start - self.position) as usize);
+                buf[..zeros].fill(0);
+                zeros
+            } else {
+                let offset = ((self.position - self.start) as usize).min(self.data.len());
+                let data = &self.data[offset..];
+                let read = buf.len().min(data.len());
+                buf[..read].copy_from_slice(&data[..read]);
+                read
+            };
+            self.position += read as u64;
+            Ok(read)
+        }
+    }
+
+    impl io::Seek for SparseCursor {
+        fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+            let end = self.start + self.data.len() as u64;
+            let position = match pos {
+                io::SeekFrom::Start(offset) => Some(offset),
+                io::SeekFrom::End(offset) => end.checked_add_signed(offset),
+                io::SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+            };
+            self.position = position
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Invalid seek"))?;
+            Ok(self.position)
+        }
+    }
+
+    #[test]
+    fn write_beyond_4gib() {
+        let start = 5 << 30;
+        let mut stream = SparseCursor {
+            start,
+            data: Vec::new(),
+            position: 0,
+        };
+        io::Seek::seek(&mut stream, io::SeekFrom::Start(start)).unwrap();
+
+        let mut writer = ZipWriter::new(stream);
+        for name in ["first.txt", "second.txt"] {
+            writer.start_file(name, FileOptions::default()).unwrap();
+            writer.write_all(name.as_bytes()).unwrap();
+        }
+        let stream = writer.finish().unwrap();
+        assert!(has_zip64_end(&stream.data));
+
+        let mut archive = ZipArchive::new(stream).unwrap();
+        assert_eq!(archive.len(), 2);
+        for name in ["first.txt", "second.txt"] {
+            let mut file = archive.by_name(name).unwrap();
+            assert!(file.header_start() > u32::MAX as u64);
+            let mut contents = String::new();
+            file.read_to_string(&mut contents).unwrap();
+            assert_eq!(contents, name);
+        }
+    }
+
+    #[test]
+    fn write_force_zip64() {
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        writer.set_force_zip64(true);
+        writer
+            .start_file("file.txt", FileOptions::default())
+            .unwrap();
+        writer.write_all(b"zip64").unwrap();
+        let result = writer.finish().unwrap().into_inner();
+        assert!(has_zip64_end(&result));
+
+        let mut archive = ZipArchive::new(io::Cursor::new(result)).unwrap();
+        let mut contents = String::new();
+        archive
+            .by_name("file.txt")
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "zip64");
+    }
+
+    #[test]
+    fn write_deduplicated_same_crc32() {
+        let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        writer.set_deduplicate(true);
+        // Different contents of the same size and CRC32
+        for (name, contents) in [("a.txt", b"plumless"), ("b.txt", b"buckeroo")] {
+            writer.start_file(name, options.clone()).unwrap();
+            writer.write_all(contents).unwrap();
+        }
+        let mut archive = ZipArchive::new(writer.finish().unwrap()).unwrap();
+        assert!(writer.duplicates().is_empty());
+
+        let mut contents = String::new();
+        archive
+            .by_name("b.txt")
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "buckeroo");
+    }
+
+    #[test]
+    fn write_deduplicated() {
+        let contents = b"the same contents, many times over".repeat(100);
+        let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        writer.set_deduplicate(true);
+        for name in ["a.bin", "b.bin", "c.bin"] {
+            writer.start_file(name, options.clone()).unwrap();
+            writer.write_all(&contents).unwrap();
+        }
+        writer.start_file("other.bin", options.clone()).unwrap();
+        writer.write_all(b"other").unwrap();
+        writer
+            .start_file(
+                "secret.bin",
+                options.with_deprecated_encryption(b"password"),
+            )
+            .unwrap();
+        writer.write_all(&contents).unwrap();
+
+        let result = writer.finish().unwrap().into_inner();
+        assert!(result.len() < 3 * contents.len());
+
+        let duplicates: Vec<_> = writer
+            .duplicates()
+            .iter()
+            .map(|duplicate| (duplicate.name.as_str(), duplicate.deduplicated))
+            .collect();
+        assert_eq!(
+            duplicates,
+            [("b.bin", true), ("c.bin", true), ("secret.bin", false)]
+        );
+        assert!(writer.duplicates().iter().all(|d| d.original == "a.bin"));
+
+        let mut archive = ZipArchive::new(io::Cursor::new(result)).unwrap();
+        for name in ["a.bin", "b.bin", "c.bin"] {
+            let mut file = archive.by_name(name).unwrap();
+            let mut read = Vec::new();
+            file.read_to_end(&mut read).unwrap();
+            assert_eq!(read, contents);
+        }
+        let mut read = Vec::new();
+        archive
+            .by_name_decrypt("secret.bin", b"password")
+            .unwrap()
+            .unwrap()
+            .read_to_end(&mut read)
+            .unwrap();
+        assert_eq!(read, contents);
+    }
+
+    #[test]
+    fn write_deduplicated_last_file() {
+        // The data of the last file is dropped after the shorter central directory is written
+        let contents = vec![42; 100_000];
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        writer.set_deduplicate(true);
+        for name in ["first.bin", "last.bin"] {
+            writer.start_file(name, FileOptions::default()).unwrap();
+            writer.write_all(&contents).unwrap();
+        }
+        let result = writer.finish().unwrap().into_inner();
+
+        let mut archive = ZipArchive::new(io::Cursor::new(result)).unwrap();
+        let mut read = Vec::new();
+        archive
+            .by_name("last.bin")
+            .unwrap()
+            .read_to_end(&mut read)
+            .unwrap();
+        assert_eq!(read, contents);
+    }
 }
 
 #[cfg(not(feature = "unreserved"))]
@@ -4,6 +4,7 @@
 //! [https://courses.cs.ut.ee/MTAT.07.022/2015_fall/uploads/Main/dmitri-report-f15-16.pdf](https://courses.cs.ut.ee/MTAT.07.022/2015_fall/uploads/Main/dmitri-report-f15-16.pdf)
 
 use std::num::Wrapping;
+use zeroize::{Zeroize, Zeroizing};
 
 /// A container to hold the current key state
 #[derive(Clone, Copy)]
@@ -59,10 +60,18 @@ impl ZipCryptoKeys {
     }
 }
 
+impl Zeroize for ZipCryptoKeys {
+    fn zeroize(&mut self) {
+        self.key_0.0.zeroize();
+        self.key_1.0.zeroize();
+        self.key_2.0.zeroize();
+    }
+}
+
 /// A ZipCrypto reader with unverified password
 pub struct ZipCryptoReader<R> {
     file: R,
-    keys: ZipCryptoKeys,
+    keys: Zeroizing<ZipCryptoKeys>,
 }
 
 pub enum ZipCryptoValidator {
@@ -80,7 +89,7 @@ impl<R: std::io::Read> ZipCryptoReader<R> {
     pub fn new(file: R, password: &[u8]) -> ZipCryptoReader<R> {
         ZipCryptoReader {
             file,
-            keys: ZipCryptoKeys::derive(password),
+            keys: Zeroizing::new(ZipCryptoKeys::derive(password)),
         }
     }
 
@@ -126,7 +135,7 @@ impl<R: std::io::Read> ZipCryptoReader<R> {
 pub(crate) struct ZipCryptoWriter<W> {
     pub(crate) writer: W,
     pub(crate) buffer: Vec<u8>,
-    pub(crate) keys: ZipCryptoKeys,
+    pub(crate) keys: Zeroizing<ZipCryptoKeys>,
 }
 impl<W: std::io::Write> ZipCryptoWriter<W> {
     pub(crate) fn finish(mut self, crc32: u32) -> std::io::Result<W> {
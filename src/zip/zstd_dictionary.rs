@@ -0,0 +1,203 @@
+//! Zstd dictionaries shared by the entries of an archive
+//!
+//! A dictionary is stored in a `Stored` entry named after its id, see [`name`], and the
+//! entries compressed with it carry an extra field with the same id. Readers that don't know
+//! about this extra field still see a valid archive, but cannot decompress these entries.
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::zip::{compression::CompressionMethod, types::ZipFileData};
+
+/// Header ID of the extra field referencing a dictionary ("ZD")
+const EXTRA_FIELD_ID: u16 = 0x445a;
+
+/// Magic number starting the dictionaries in the zstd format
+#[cfg(feature = "zstd")]
+const DICTIONARY_MAGIC: u32 = 0xec30a437;
+
+/// Directory of the entries storing the dictionaries
+const DIRECTORY: &str = ".zstd-dictionaries/";
+
+/// Name of the entry storing the dictionary with the given id
+#[cfg(feature = "zstd")]
+pub(crate) fn name(id: u32) -> String {
+    format!("{DIRECTORY}{id:08x}")
+}
+
+/// Whether the file `data` stores a dictionary rather than the contents of the archive
+///
+/// This only looks at the name and compression method, so it doesn't need the `zstd` feature.
+pub(crate) fn is_dictionary(data: &ZipFileData) -> bool {
+    data.compression_method == CompressionMethod::Stored
+        && data
+            .file_name
+            .strip_prefix(DIRECTORY)
+            .is_some_and(|id| id.len() == 8 && id.bytes().all(|b| b.is_ascii_hexdigit()))
+}
+
+/// The extra field referencing the dictionary with the given id
+pub(crate) fn extra_field(id: u32) -> Vec<u8> {
+    let mut field = vec![0; 8];
+    LittleEndian::write_u16(&mut field, EXTRA_FIELD_ID);
+    LittleEndian::write_u16(&mut field[2..], 4);
+    LittleEndian::write_u32(&mut field[4..], id);
+    field
+}
+
+/// The id of the dictionary referenced by an extra field, if any
+pub(crate) fn referenced_id(mut extra_field: &[u8]) -> Option<u32> {
+    while extra_field.len() >= 4 {
+        let kind = LittleEndian::read_u16(extra_field);
+        let len = LittleEndian::read_u16(&extra_field[2..]) as usize;
+        let data = extra_field.get(4..4 + len)?;
+        if kind == EXTRA_FIELD_ID && len == 4 {
+            return Some(LittleEndian::read_u32(data));
+        }
+        extra_field = &extra_field[4 + len..];
+    }
+    None
+}
+
+/// The id of the dictionary that the file `data` has to be decompressed with, if any
+#[cfg(feature = "zstd")]
+pub(crate) fn used_by(data: &ZipFileData) -> Option<u32> {
+    if data.compression_method != CompressionMethod::Zstd {
+        return None;
+    }
+    referenced_id(&data.extra_field)
+}
+
+/// The id of a dictionary in the zstd format, which is never zero
+#[cfg(feature = "zstd")]
+pub(crate) fn dictionary_id(dictionary: &[u8]) -> Option<u32> {
+    if dictionary.len() < 8 || LittleEndian::read_u32(dictionary) != DICTIONARY_MAGIC {
+        return None;
+    }
+    Some(LittleEndian::read_u32(&dictionary[4..])).filter(|&id| id != 0)
+}
+
+#[cfg(all(test, feature = "zstd"))]
+mod test {
+    use crate::zip::compression::CompressionMethod;
+    use crate::zip::read::{LazyZipArchive, ZipArchive};
+    use crate::zip::result::ZipError;
+    use crate::zip::write::{FileOptions, ZipWriter};
+    use std::io::{self, Read, Write};
+
+    fn sample(i: usize) -> String {
+        format!(
+            "{{\"id\": {i}, \"name\": \"sensor-{}\", \"status\": \"{}\", \"reading\": {}}}\n",
+            i % 17,
+            ["ok", "degraded", "offline"][i % 3],
+            i * 37 % 1000
+        )
+    }
+
+    fn archive(dictionary: bool) -> Vec<u8> {
+        let samples: Vec<_> = (0..1000).map(sample).collect();
+        let options = FileOptions::default().compression_method(CompressionMethod::Zstd);
+
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        if dictionary {
+            writer.train_zstd_dictionary(&samples, 4096).unwrap();
+        }
+        for (i, sample) in samples.iter().enumerate().take(200) {
+            writer
+                .start_file(format!("{i}.json"), options.clone())
+                .unwrap();
+            writer.write_all(sample.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn zstd_dictionary_roundtrip() {
+        let bytes = archive(true);
+        assert!(bytes.len() < archive(false).len());
+
+        let mut archive = ZipArchive::new(io::Cursor::new(bytes.clone())).unwrap();
+        assert_eq!(archive.len(), 201);
+        assert!(archive
+            .by_index(0)
+            .unwrap()
+            .name()
+            .starts_with(".zstd-dictionaries/"));
+        for i in 0..200 {
+            let mut contents = String::new();
+            archive
+                .by_name(&format!("{i}.json"))
+                .unwrap()
+                .read_to_string(&mut contents)
+                .unwrap();
+            assert_eq!(contents, sample(i));
+        }
+
+        let mut contents = String::new();
+        let slices = ZipArchive::new(io::Cursor::new(bytes.clone())).unwrap();
+        slices
+            .by_name_slice_reader("42.json")
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, sample(42));
+
+        let mut lazy = LazyZipArchive::new(io::Cursor::new(bytes)).unwrap();
+        let mut contents = String::new();
+        lazy.by_name("42.json")
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, sample(42));
+
+        // Readers without the dictionary fail to decompress the entries
+        let index = (0..archive.len())
+            .find(|&i| archive.by_index_raw(i).unwrap().name() == "42.json")
+            .unwrap();
+        let mut raw = Vec::new();
+        archive
+            .by_index_raw(index)
+            .unwrap()
+            .read_to_end(&mut raw)
+            .unwrap();
+        assert!(zstd::decode_all(raw.as_slice()).is_err());
+    }
+
+    #[test]
+    fn zstd_dictionary_hidden() {
+        let bytes = archive(true);
+        let lazy = LazyZipArchive::new(io::Cursor::new(bytes.clone())).unwrap();
+        assert_eq!(lazy.metadata_index().unwrap().len(), 200);
+
+        let mut archive = ZipArchive::new(io::Cursor::new(bytes)).unwrap();
+        assert!(archive.by_index(0).unwrap().is_zstd_dictionary());
+        assert!(!archive.by_index(1).unwrap().is_zstd_dictionary());
+        assert_eq!(archive.metadata_index().len(), 200);
+        assert!(archive.tree().get(".zstd-dictionaries").is_none());
+
+        let directory =
+            std::env::temp_dir().join(format!("zip_zstd_dictionary_{}", std::process::id()));
+        archive.extract(&directory).unwrap();
+        let dictionaries = directory.join(".zstd-dictionaries").exists();
+        let extracted = std::fs::read_dir(&directory).unwrap().count();
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert!(!dictionaries);
+        assert_eq!(extracted, 200);
+    }
+
+    #[test]
+    fn zstd_dictionary_missing() {
+        let mut source = ZipArchive::new(io::Cursor::new(archive(true))).unwrap();
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        writer
+            .raw_copy_file(source.by_name("42.json").unwrap())
+            .unwrap();
+        let mut copy = ZipArchive::new(writer.finish().unwrap()).unwrap();
+        assert!(matches!(
+            copy.by_index(0).err().unwrap().without_context(),
+            ZipError::InvalidArchive("Missing zstd dictionary")
+        ));
+
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        assert!(writer.set_zstd_dictionary(b"not a dictionary").is_err());
+    }
+}